@@ -0,0 +1,669 @@
+use std::{
+    fmt,
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    process::Command,
+    sync::LazyLock,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::{error, info, warn};
+use regex::Regex;
+use serde_json::json;
+
+use crate::{
+    config::{AlertConfig, Config},
+    http,
+    snapshot::Snapshot,
+};
+
+static RULE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^\s*(?P<condition>.+?)(?:\s+for\s+(?P<for>\S+))?(?:\s+while\s+(?P<while>.+?))?\s*$",
+    )
+    .unwrap()
+});
+
+static CONDITION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^\s*(?P<metric>[\w.\-]+)\s*(?P<operator>>=|<=|==|!=|>|<)\s*(?P<value>"[^"]*"|\S+)\s*$"#,
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // One decimal is enough for all the metrics
+            Value::Number(number) => write!(f, "{}", (number * 10.0).round() / 10.0),
+            Value::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Metric {
+    /// Temperature of a thermal zone in °C
+    Temperature(String),
+    /// Power of a rail in mW
+    Power(String),
+    /// Average utilization of the cores in %
+    CpuLoad,
+    /// Utilization of a core in %
+    Core(String),
+    GpuLoad,
+    /// MHz
+    GpuFrequency,
+    /// Used RAM in % of the total
+    Ram,
+    /// Used swap in % of the total
+    Swap,
+    /// RPM of a fan, or of the fastest one
+    FanRpm(Option<String>),
+    /// Name of the nvpmodel mode
+    Nvpmodel,
+}
+
+impl Metric {
+    fn parse(metric: &str) -> Result<Self> {
+        let (group, name) = match metric.split_once('.') {
+            Some((group, name)) => (group, Some(name)),
+            None => (metric, None),
+        };
+
+        let metric = match (group, name) {
+            ("thermal", Some(zone)) => Metric::Temperature(zone.to_string()),
+            ("power", Some(rail)) => Metric::Power(rail.to_string()),
+            ("cpu", Some("load")) => Metric::CpuLoad,
+            ("cpu", Some(core)) => Metric::Core(core.to_string()),
+            ("gpu", Some("load")) => Metric::GpuLoad,
+            ("gpu", Some("frequency")) => Metric::GpuFrequency,
+            ("memory", Some("ram")) => Metric::Ram,
+            ("memory", Some("swap")) => Metric::Swap,
+            ("fan", Some("rpm")) => Metric::FanRpm(None),
+            ("fan", Some(fan)) => Metric::FanRpm(Some(fan.to_string())),
+            ("nvpmodel", None) => Metric::Nvpmodel,
+            _ => bail!("Unknown metric {}", metric),
+        };
+
+        Ok(metric)
+    }
+
+    fn value(&self, snapshot: &Snapshot, config: &Config) -> Option<Value> {
+        let number = match self {
+            // Zones and rails match their name or their display name
+            Metric::Temperature(zone) => snapshot
+                .thermal
+                .sensors
+                .iter()
+                .find(|sensor| {
+                    sensor.name.eq_ignore_ascii_case(zone)
+                        || config.thermal.name(&sensor.name).eq_ignore_ascii_case(zone)
+                })
+                .filter(|sensor| sensor.temperature >= -25.0)
+                .map(|sensor| sensor.temperature as f64),
+            Metric::Power(rail) => snapshot
+                .power
+                .channels
+                .iter()
+                .find(|channel| {
+                    channel.name.eq_ignore_ascii_case(rail)
+                        || config.power.name(&channel.name).eq_ignore_ascii_case(rail)
+                })
+                .map(|channel| channel.power as f64),
+            Metric::CpuLoad => {
+                let cores = &snapshot.cpu.cores;
+                if cores.is_empty() {
+                    None
+                } else {
+                    Some(
+                        cores.iter().map(|core| core.utilization).sum::<f64>() / cores.len() as f64,
+                    )
+                }
+            }
+            Metric::Core(name) => snapshot
+                .cpu
+                .cores
+                .iter()
+                .find(|core| core.name.eq_ignore_ascii_case(name))
+                .map(|core| core.utilization),
+            Metric::GpuLoad => snapshot.gpu.load,
+            Metric::GpuFrequency => snapshot.gpu.frequency.map(|frequency| frequency as f64),
+            Metric::Ram => snapshot
+                .memory
+                .mem
+                .as_ref()
+                .filter(|mem| mem.total_ram > 0.0)
+                .map(|mem| mem.used_ram / mem.total_ram * 100.0),
+            Metric::Swap => snapshot
+                .memory
+                .mem
+                .as_ref()
+                .filter(|mem| mem.total_swap > 0.0)
+                .map(|mem| mem.used_swap / mem.total_swap * 100.0),
+            Metric::FanRpm(None) => snapshot.fan.rpm().map(|rpm| rpm as f64),
+            Metric::FanRpm(Some(name)) => snapshot
+                .fan
+                .fans
+                .iter()
+                .find(|fan| fan.name.eq_ignore_ascii_case(name))
+                .and_then(|fan| fan.rpm)
+                .map(|rpm| rpm as f64),
+            Metric::Nvpmodel => {
+                return snapshot
+                    .power
+                    .mode
+                    .as_ref()
+                    .map(|mode| Value::Text(mode.name.clone()));
+            }
+        };
+
+        number.map(Value::Number)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    metric: Metric,
+    operator: Operator,
+    value: Value,
+}
+
+impl Condition {
+    fn parse(condition: &str) -> Result<Self> {
+        let captures = CONDITION
+            .captures(condition)
+            .ok_or(anyhow!("Invalid condition {}", condition))?;
+
+        let metric = Metric::parse(&captures["metric"])?;
+
+        let operator = match &captures["operator"] {
+            ">" => Operator::Greater,
+            ">=" => Operator::GreaterOrEqual,
+            "<" => Operator::Less,
+            "<=" => Operator::LessOrEqual,
+            "==" => Operator::Equal,
+            _ => Operator::NotEqual,
+        };
+
+        let value = match captures["value"].parse::<f64>() {
+            Ok(number) => Value::Number(number),
+            Err(_) => Value::Text(captures["value"].trim_matches('"').to_string()),
+        };
+
+        if let Value::Text(_) = value
+            && !matches!(operator, Operator::Equal | Operator::NotEqual)
+        {
+            bail!("Only == and != compare text in {}", condition);
+        }
+
+        Ok(Self {
+            metric,
+            operator,
+            value,
+        })
+    }
+
+    /// The current value of the metric when the condition holds
+    fn evaluate(&self, snapshot: &Snapshot, config: &Config) -> Option<Value> {
+        let value = self.metric.value(snapshot, config)?;
+
+        let holds = match (&value, &self.value) {
+            (Value::Number(value), Value::Number(threshold)) => match self.operator {
+                Operator::Greater => value > threshold,
+                Operator::GreaterOrEqual => value >= threshold,
+                Operator::Less => value < threshold,
+                Operator::LessOrEqual => value <= threshold,
+                Operator::Equal => value == threshold,
+                Operator::NotEqual => value != threshold,
+            },
+            (Value::Text(value), Value::Text(expected)) => match self.operator {
+                Operator::Equal => value.eq_ignore_ascii_case(expected),
+                _ => !value.eq_ignore_ascii_case(expected),
+            },
+            _ => false,
+        };
+
+        holds.then_some(value)
+    }
+}
+
+/// Parses `30`, `30s`, `5m` or `1h` to seconds
+fn parse_duration(duration: &str) -> Result<f64> {
+    let (number, unit) = match duration.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(index) => duration.split_at(index),
+        None => (duration, "s"),
+    };
+
+    let number = number
+        .parse::<f64>()
+        .context(format!("Invalid duration {}", duration))?;
+
+    match unit {
+        "s" => Ok(number),
+        "m" => Ok(number * 60.0),
+        "h" => Ok(number * 3600.0),
+        _ => bail!("Invalid duration {}, expected s, m or h", duration),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Firing,
+    Resolved,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Firing => write!(f, "firing"),
+            State::Resolved => write!(f, "resolved"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Alert {
+    pub name: String,
+    pub rule: String,
+    condition: Condition,
+    /// Seconds the condition must hold before firing
+    duration: f64,
+    guard: Option<Condition>,
+    command: Option<String>,
+    file: Option<PathBuf>,
+    webhook: Option<(String, String)>,
+    /// Time the condition started to hold
+    since: Option<f64>,
+    pub firing: bool,
+    /// Last value of the metric while the condition holds
+    pub value: Option<String>,
+}
+
+impl Alert {
+    fn new(config: &AlertConfig) -> Result<Self> {
+        let captures = RULE
+            .captures(&config.rule)
+            .ok_or(anyhow!("Invalid alert rule {}", config.rule))?;
+
+        let context = || format!("Invalid alert rule {}", config.rule);
+
+        let condition = Condition::parse(&captures["condition"]).with_context(context)?;
+        let duration = match captures.name("for") {
+            Some(duration) => parse_duration(duration.as_str()).with_context(context)?,
+            None => 0.0,
+        };
+        let guard = captures
+            .name("while")
+            .map(|guard| Condition::parse(guard.as_str()))
+            .transpose()
+            .with_context(context)?;
+
+        let webhook = config
+            .webhook
+            .as_ref()
+            .map(|url| {
+                http::parse_url(url, "/").map(|(host, path)| (host.to_string(), path.to_string()))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            name: config.name.clone().unwrap_or(config.rule.clone()),
+            rule: config.rule.clone(),
+            condition,
+            duration,
+            guard,
+            command: config.command.clone(),
+            file: config.file.clone(),
+            webhook,
+            since: None,
+            firing: false,
+            value: None,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.since = None;
+        self.firing = false;
+        self.value = None;
+    }
+
+    /// Returns the new state when the alert fires or resolves
+    fn evaluate(&mut self, time: f64, snapshot: &Snapshot, config: &Config) -> Option<State> {
+        let guarded = match &self.guard {
+            Some(guard) => guard.evaluate(snapshot, config).is_some(),
+            None => true,
+        };
+
+        match self.condition.evaluate(snapshot, config) {
+            Some(value) if guarded => {
+                self.value = Some(value.to_string());
+                let since = *self.since.get_or_insert(time);
+
+                if !self.firing && time - since >= self.duration {
+                    self.firing = true;
+                    return Some(State::Firing);
+                }
+                None
+            }
+            _ => {
+                self.since = None;
+                if self.firing {
+                    self.firing = false;
+                    return Some(State::Resolved);
+                }
+                self.value = None;
+                None
+            }
+        }
+    }
+
+    /// Logs the state change and runs the actions of the alert in the background
+    fn notify(&self, state: State) {
+        let value = self.value.clone().unwrap_or_default();
+
+        match state {
+            State::Firing => warn!("Alert {} firing: {} ({})", self.name, self.rule, value),
+            State::Resolved => info!("Alert {} resolved: {}", self.name, self.rule),
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|timestamp| timestamp.as_secs_f64())
+            .unwrap_or_default();
+
+        let payload = json!({
+            "alert": self.name,
+            "rule": self.rule,
+            "state": state.to_string(),
+            "value": value,
+            "timestamp": timestamp,
+        })
+        .to_string();
+
+        if let Some(command) = &self.command {
+            let spawned = Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .env("TEGRATOP_ALERT", &self.name)
+                .env("TEGRATOP_RULE", &self.rule)
+                .env("TEGRATOP_STATE", state.to_string())
+                .env("TEGRATOP_VALUE", &value)
+                .spawn();
+
+            match spawned {
+                // Reap the child without blocking the UI
+                Ok(mut child) => {
+                    thread::spawn(move || child.wait());
+                }
+                Err(e) => error!("Failed to run {}: {}", command, e),
+            }
+        }
+
+        if let Some(path) = &self.file {
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", payload));
+
+            if let Err(e) = written {
+                error!("Failed to write to {}: {}", path.display(), e);
+            }
+        }
+
+        if let Some((host, path)) = self.webhook.clone() {
+            thread::spawn(move || {
+                if let Err(e) = http::post(&host, &path, "application/json", &payload) {
+                    error!("{}", e);
+                }
+            });
+        }
+    }
+}
+
+/// The alert rules of the configuration, evaluated at every tick.
+#[derive(Debug, Default)]
+pub struct Alerts {
+    pub alerts: Vec<Alert>,
+    last_time: Option<f64>,
+}
+
+impl Alerts {
+    pub fn new(configs: &[AlertConfig]) -> Result<Self> {
+        let alerts = configs
+            .iter()
+            .map(Alert::new)
+            .collect::<Result<Vec<Alert>>>()?;
+
+        Ok(Self {
+            alerts,
+            last_time: None,
+        })
+    }
+
+    pub fn firing(&self) -> impl Iterator<Item = &Alert> {
+        self.alerts.iter().filter(|alert| alert.firing)
+    }
+
+    /// Evaluates the rules against a new sample. The actions only run when `actions` is set,
+    /// e.g. not during a replay.
+    pub fn evaluate(&mut self, time: f64, snapshot: &Snapshot, config: &Config, actions: bool) {
+        match self.last_time {
+            Some(last_time) if last_time == time => return,
+            // Stepping back in a replay
+            Some(last_time) if last_time > time => {
+                self.alerts.iter_mut().for_each(Alert::reset);
+            }
+            _ => {}
+        }
+        self.last_time = Some(time);
+
+        for alert in self.alerts.iter_mut() {
+            if let Some(state) = alert.evaluate(time, snapshot, config) {
+                if actions {
+                    alert.notify(state);
+                } else {
+                    info!("Alert {} {}: {}", alert.name, state, alert.rule);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::power::Mode;
+
+    fn alert(rule: &str) -> Result<Alert> {
+        Alert::new(&AlertConfig {
+            rule: rule.to_string(),
+            ..AlertConfig::default()
+        })
+    }
+
+    fn snapshot(gpu_load: f64, mode: &str) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        snapshot.gpu.load = Some(gpu_load);
+        snapshot.power.mode = Some(Mode {
+            name: mode.to_string(),
+            ..Mode::default()
+        });
+        snapshot
+    }
+
+    #[test]
+    fn rule() {
+        let alert =
+            alert("thermal.CPU-therm >= 85.5 for 5m while nvpmodel != \"MODE 15W\"").unwrap();
+
+        assert_eq!(
+            alert.condition,
+            Condition {
+                metric: Metric::Temperature("CPU-therm".to_string()),
+                operator: Operator::GreaterOrEqual,
+                value: Value::Number(85.5),
+            }
+        );
+        assert_eq!(alert.duration, 300.0);
+        assert_eq!(
+            alert.guard,
+            Some(Condition {
+                metric: Metric::Nvpmodel,
+                operator: Operator::NotEqual,
+                value: Value::Text("MODE 15W".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn condition() {
+        let condition = Condition::parse("fan.pwmfan0<1000").unwrap();
+        assert_eq!(
+            condition.metric,
+            Metric::FanRpm(Some("pwmfan0".to_string()))
+        );
+        assert_eq!(condition.operator, Operator::Less);
+        assert_eq!(Metric::parse("fan.rpm").unwrap(), Metric::FanRpm(None));
+        assert_eq!(Metric::parse("cpu.load").unwrap(), Metric::CpuLoad);
+
+        for invalid in [
+            "gpu.load >",
+            "gpu.load => 50",
+            "gpu.load > busy",
+            "disk.sda > 50",
+            "nvpmodel.mode == MAXN",
+        ] {
+            assert!(Condition::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn duration() {
+        assert_eq!(parse_duration("30").unwrap(), 30.0);
+        assert_eq!(parse_duration("30s").unwrap(), 30.0);
+        assert_eq!(parse_duration("1.5m").unwrap(), 90.0);
+        assert_eq!(parse_duration("1h").unwrap(), 3600.0);
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(alert("gpu.load > 50 for soon").is_err());
+    }
+
+    #[test]
+    fn webhook() {
+        // Resolved when posting, not when loading
+        let config = AlertConfig {
+            rule: "gpu.load > 50".to_string(),
+            webhook: Some("http://alerts.invalid:8080/hook".to_string()),
+            ..AlertConfig::default()
+        };
+        assert!(Alert::new(&config).is_ok());
+        for invalid in [
+            "https://example.com/hook",
+            "http://example.com/hook",
+            "http://:80/",
+        ] {
+            let config = AlertConfig {
+                rule: "gpu.load > 50".to_string(),
+                webhook: Some(invalid.to_string()),
+                ..AlertConfig::default()
+            };
+            assert!(Alert::new(&config).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn fires_after_duration() {
+        let config = Config::default();
+        let mut alert = alert("gpu.load > 50 for 2s").unwrap();
+
+        assert_eq!(alert.evaluate(0.0, &snapshot(60.0, "MAXN"), &config), None);
+        assert_eq!(alert.evaluate(1.0, &snapshot(70.0, "MAXN"), &config), None);
+        assert_eq!(
+            alert.evaluate(2.0, &snapshot(80.0, "MAXN"), &config),
+            Some(State::Firing)
+        );
+        assert!(alert.firing);
+        assert_eq!(alert.value.as_deref(), Some("80"));
+        assert_eq!(alert.evaluate(3.0, &snapshot(90.0, "MAXN"), &config), None);
+
+        // The value stays for the resolved notification, then it is cleared
+        assert_eq!(
+            alert.evaluate(4.0, &snapshot(10.0, "MAXN"), &config),
+            Some(State::Resolved)
+        );
+        assert_eq!(alert.value.as_deref(), Some("90"));
+        assert_eq!(alert.evaluate(5.0, &snapshot(10.0, "MAXN"), &config), None);
+        assert_eq!(alert.value, None);
+
+        // A dip restarts the duration
+        alert.evaluate(6.0, &snapshot(60.0, "MAXN"), &config);
+        alert.evaluate(7.0, &snapshot(10.0, "MAXN"), &config);
+        assert_eq!(alert.evaluate(8.0, &snapshot(60.0, "MAXN"), &config), None);
+        assert_eq!(alert.evaluate(9.0, &snapshot(60.0, "MAXN"), &config), None);
+        assert_eq!(
+            alert.evaluate(10.0, &snapshot(60.0, "MAXN"), &config),
+            Some(State::Firing)
+        );
+    }
+
+    #[test]
+    fn guard() {
+        let config = Config::default();
+        let mut alert = alert("gpu.load > 50 while nvpmodel == maxn").unwrap();
+
+        assert_eq!(
+            alert.evaluate(0.0, &snapshot(60.0, "MODE_15W"), &config),
+            None
+        );
+        assert_eq!(
+            alert.evaluate(1.0, &snapshot(60.0, "MAXN"), &config),
+            Some(State::Firing)
+        );
+        // Resolved when the guard stops holding too
+        assert_eq!(
+            alert.evaluate(2.0, &snapshot(60.0, "MODE_15W"), &config),
+            Some(State::Resolved)
+        );
+    }
+
+    #[test]
+    fn replay_step_back() {
+        let config = Config::default();
+        let mut alerts = Alerts::new(&[AlertConfig {
+            rule: "gpu.load > 50 for 10s".to_string(),
+            ..AlertConfig::default()
+        }])
+        .unwrap();
+
+        alerts.evaluate(0.0, &snapshot(60.0, "MAXN"), &config, false);
+        alerts.evaluate(10.0, &snapshot(60.0, "MAXN"), &config, false);
+        assert_eq!(alerts.firing().count(), 1);
+
+        // Stepping back resets the alerts, the duration starts over from the older sample
+        alerts.evaluate(1.0, &snapshot(60.0, "MAXN"), &config, false);
+        assert_eq!(alerts.firing().count(), 0);
+        alerts.evaluate(11.0, &snapshot(60.0, "MAXN"), &config, false);
+        assert_eq!(alerts.firing().count(), 1);
+    }
+}
@@ -1,43 +1,88 @@
-use std::error;
+use std::{
+    error,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Instant,
+};
+
+use anyhow::{Result, anyhow};
+
+use strum::IntoEnumIterator;
 
 use crate::{
-    board::Board, cpu::CPU, disk::Disk, engine::Engine, fan::Fan, gpu::GPU, memory::Memory,
-    network::Network, power::Power, system::System, thermal::Thermal,
+    alert::Alerts,
+    chart::ChartView,
+    collector::{Collector, Collectors},
+    config::Config,
+    history::History,
+    popup::{Action, Popup},
+    process::{ProcessView, Processes},
+    replay::Player,
+    snapshot::Snapshot,
+    ui::Panel,
 };
 
 pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 
+#[derive(Debug)]
+pub enum Source {
+    Live(Box<Collectors>),
+    Replay(Player),
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum View {
+    #[default]
+    Main,
+    Charts,
+    Processes,
+}
+
 #[derive(Debug)]
 pub struct App {
-    pub board: Board,
-    pub cpu: CPU,
-    pub disk: Disk,
-    pub engine: Engine,
-    pub fan: Fan,
-    pub gpu: GPU,
-    pub memory: Memory,
-    pub network: Network,
-    pub power: Power,
-    pub system: System,
-    pub thermal: Thermal,
+    pub source: Source,
+    pub view: View,
+    pub chart: ChartView,
+    pub process: ProcessView,
+    /// Only refreshed while the process view is open
+    pub processes: Processes,
+    pub popup: Option<Popup>,
+    pub snapshot: Snapshot,
+    pub history: History,
+    pub config: Config,
+    pub alerts: Alerts,
+    pub panels: Vec<Panel>,
+    pub no_color: bool,
+    /// Disables the actions that change the system
+    pub read_only: bool,
     pub running: bool,
+    /// Result of the action running on the worker thread
+    action: Option<Receiver<Result<Option<Popup>>>>,
+    start: Instant,
 }
 
 impl Default for App {
     fn default() -> Self {
+        let collectors = Collectors::new();
+        let snapshot = collectors.snapshot();
+
         Self {
-            board: Board::new(),
-            cpu: CPU::new(),
-            disk: Disk::new(),
-            engine: Engine::new(),
-            fan: Fan::new(),
-            gpu: GPU::new(),
-            memory: Memory::new(),
-            network: Network::new(),
-            power: Power::new(),
-            system: System::new(),
-            thermal: Thermal::new(),
+            source: Source::Live(Box::new(collectors)),
+            view: View::default(),
+            chart: ChartView::new(),
+            process: ProcessView::new(),
+            processes: Processes::new(),
+            popup: None,
+            snapshot,
+            history: History::default(),
+            config: Config::default(),
+            alerts: Alerts::default(),
+            panels: Panel::iter().collect(),
+            no_color: false,
+            read_only: false,
             running: true,
+            action: None,
+            start: Instant::now(),
         }
     }
 }
@@ -47,17 +92,82 @@ impl App {
         Self::default()
     }
 
+    pub fn replay(player: Player) -> Self {
+        Self {
+            snapshot: player.snapshot().clone(),
+            source: Source::Replay(player),
+            view: View::default(),
+            chart: ChartView::new(),
+            process: ProcessView::new(),
+            processes: Processes::new(),
+            popup: None,
+            history: History::default(),
+            config: Config::default(),
+            alerts: Alerts::default(),
+            panels: Panel::iter().collect(),
+            no_color: false,
+            read_only: false,
+            running: true,
+            action: None,
+            start: Instant::now(),
+        }
+    }
+
     pub fn tick(&mut self) {
-        self.cpu.refresh();
-        self.disk.refresh();
-        self.engine.refresh();
-        self.fan.refresh();
-        self.gpu.refresh();
-        self.memory.refresh();
-        self.network.refresh();
-        self.power.refresh();
-        self.system.refresh();
-        self.thermal.refresh();
+        let time = match &mut self.source {
+            Source::Live(collectors) => {
+                collectors.engine.scan = self.view == View::Processes
+                    || (self.view == View::Main && self.panels.contains(&Panel::Engines));
+                collectors.refresh();
+                self.snapshot = collectors.snapshot();
+                if self.view == View::Processes {
+                    // Scanning the open files of every process is costly, do it once per tick
+                    self.processes.devices = Some(collectors.engine.devices.clone());
+                    self.processes.refresh();
+                }
+                let time = self.start.elapsed().as_secs_f64();
+                self.history.push(time, &self.snapshot);
+                time
+            }
+            Source::Replay(player) => {
+                player.advance();
+                // At high speeds several samples are passed in one tick, keep them all in the graphs
+                for (time, snapshot) in player.since(self.history.last_time()) {
+                    self.history.push(time, snapshot);
+                }
+                self.snapshot = player.snapshot().clone();
+                player.elapsed()
+            }
+        };
+
+        // Only the live metrics trigger the actions of the alerts
+        let live = matches!(self.source, Source::Live(_));
+        self.alerts
+            .evaluate(time, &self.snapshot, &self.config, live);
+    }
+
+    /// Runs an action on a worker thread, `poll_action` shows its result
+    pub fn spawn(&mut self, action: Action) {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // The receiver is gone if the application quit meanwhile
+            let _ = sender.send(action.run());
+        });
+        self.action = Some(receiver);
+    }
+
+    /// Replaces the running popup by the result of the action once it returns
+    pub fn poll_action(&mut self) {
+        let Some(receiver) = &self.action else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(anyhow!("The action stopped without a result")),
+        };
+        self.action = None;
+        self.popup = Popup::result(result);
     }
 
     pub fn quit(&mut self) {
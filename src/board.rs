@@ -1,8 +1,9 @@
 use log::error;
-use std::{fs, path::Path};
+use std::fs;
 
 use anyhow::{Context, Result};
 
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
     layout::{Constraint, Rect},
@@ -11,6 +12,9 @@ use ratatui::{
 };
 use regex::Regex;
 
+use crate::{collector::Collector, root};
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Default)]
 pub struct Board {
     name: Option<String>,
@@ -18,19 +22,26 @@ pub struct Board {
     bios: Option<String>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BoardSnapshot {
+    pub name: Option<String>,
+    pub l4t: Option<String>,
+    pub bios: Option<String>,
+}
+
 impl Board {
     fn name() -> Result<String> {
-        let path = Path::new("/sys/firmware/devicetree/base/model");
+        let path = root::path("/sys/firmware/devicetree/base/model");
         let name =
-            fs::read_to_string(path).context(format!("Failed to read from {}", path.display()))?;
+            fs::read_to_string(&path).context(format!("Failed to read from {}", path.display()))?;
 
-        Ok(name)
+        Ok(name.trim_end_matches('\0').to_string())
     }
 
     fn l4t() -> Result<Option<String>> {
-        let path = Path::new("/etc/nv_tegra_release");
+        let path = root::path("/etc/nv_tegra_release");
         let l4t_buffer =
-            fs::read_to_string(path).context(format!("Failed to read from {}", path.display()))?;
+            fs::read_to_string(&path).context(format!("Failed to read from {}", path.display()))?;
 
         let re = Regex::new(r"R(\d+) \(release\), REVISION: (\d+\.\d+)")?;
 
@@ -44,14 +55,18 @@ impl Board {
     }
 
     fn bios() -> Result<Option<String>> {
-        let path = Path::new("/sys/class/dmi/id/bios_version");
+        let path = root::path("/sys/class/dmi/id/bios_version");
         let buffer =
-            fs::read_to_string(path).context(format!("Failed to read from {}", path.display()))?;
+            fs::read_to_string(&path).context(format!("Failed to read from {}", path.display()))?;
         let parts = buffer.split('-').next().map(|s| s.to_string());
         Ok(parts)
     }
+}
+
+impl Collector for Board {
+    type Snapshot = BoardSnapshot;
 
-    pub fn new() -> Self {
+    fn init() -> Result<Self> {
         let name = Board::name().map_or_else(
             |e| {
                 error!("{}", e);
@@ -76,9 +91,23 @@ impl Board {
             }
         };
 
-        Self { name, l4t, bios }
+        Ok(Self { name, l4t, bios })
     }
 
+    // The board information does not change
+    fn refresh(&mut self) {}
+
+    fn snapshot(&self) -> BoardSnapshot {
+        BoardSnapshot {
+            name: self.name.clone(),
+            l4t: self.l4t.clone(),
+            bios: self.bios.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl BoardSnapshot {
     pub fn render(&self, frame: &mut Frame, block: Rect) {
         let rows: [Row; 3] = [
             Row::new(vec![
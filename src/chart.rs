@@ -0,0 +1,318 @@
+use std::collections::HashSet;
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    symbols,
+    text::{Line, Span},
+    widgets::{
+        Axis, Block, Borders, Chart, Dataset, GraphType, List, ListItem, ListState, Padding,
+    },
+};
+
+use crate::{
+    config::Config,
+    history::{History, Series},
+    snapshot::Snapshot,
+};
+
+/// Time windows in seconds
+const WINDOWS: [u64; 4] = [60, 300, 900, 3600];
+
+/// Samples kept by default at most, 10 per second for the longest window. Below 100 ms the
+/// longest window is only partly filled rather than keeping millions of points per series.
+const MAX_HISTORY_LENGTH: u64 = 36_000;
+
+/// Number of samples needed to fill the longest window when sampling every `interval` milliseconds
+pub fn history_length(interval: u64) -> usize {
+    (WINDOWS[WINDOWS.len() - 1] * 1000)
+        .div_ceil(interval.max(1))
+        .min(MAX_HISTORY_LENGTH) as usize
+}
+
+const COLORS: [Color; 12] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Green,
+    Color::Red,
+    Color::Blue,
+    Color::LightCyan,
+    Color::LightYellow,
+    Color::LightMagenta,
+    Color::LightGreen,
+    Color::LightRed,
+    Color::LightBlue,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Group {
+    Power,
+    Thermal,
+}
+
+type Key = (Group, String);
+
+/// Full screen view of the power rails and thermal zones over time.
+#[derive(Debug, Default)]
+pub struct ChartView {
+    window: usize,
+    selected: usize,
+    hidden: HashSet<Key>,
+}
+
+impl ChartView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Width of the time window in seconds
+    pub fn window(&self) -> u64 {
+        WINDOWS[self.window]
+    }
+
+    pub fn set_window(&mut self, index: usize) {
+        if index < WINDOWS.len() {
+            self.window = index;
+        }
+    }
+
+    pub fn select_next(&mut self, history: &History, config: &Config) {
+        let count = series(history, config).len();
+        if count > 0 {
+            self.selected = (self.selected + 1).min(count - 1);
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Shows or hides the selected series
+    pub fn toggle(&mut self, history: &History, config: &Config) {
+        if let Some(key) = series(history, config).into_iter().nth(self.selected)
+            && !self.hidden.remove(&key)
+        {
+            self.hidden.insert(key);
+        }
+    }
+
+    pub fn show_all(&mut self) {
+        self.hidden.clear();
+    }
+
+    pub fn render(
+        &self,
+        snapshot: &Snapshot,
+        history: &History,
+        config: &Config,
+        frame: &mut Frame,
+        block: Rect,
+    ) {
+        let (charts_block, legend_block) = {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(1), Constraint::Length(36)])
+                .split(block);
+
+            (chunks[0], chunks[1])
+        };
+
+        let (power_block, thermal_block) = {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(charts_block);
+
+            (chunks[0], chunks[1])
+        };
+
+        let keys = series(history, config);
+        let unit = config.temperature_unit;
+        let now = history.last_time().unwrap_or_default();
+        let window = self.window() as f64;
+
+        // Points in the window, relative to the last sample, in the temperature unit
+        let points = |group: Group, series: &Series| -> Vec<(f64, f64)> {
+            series
+                .points()
+                .filter(|(time, _)| now - time <= window)
+                .map(|(time, value)| match group {
+                    Group::Power => (time - now, *value),
+                    Group::Thermal => (time - now, unit.convert(*value as f32) as f64),
+                })
+                .collect()
+        };
+
+        let data = keys
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| !self.hidden.contains(key))
+            .filter_map(|(index, (group, name))| {
+                let series = match group {
+                    Group::Power => history.power.get(name),
+                    Group::Thermal => history.thermal.get(name),
+                }?;
+                Some((
+                    (*group, name.clone()),
+                    points(*group, series),
+                    COLORS[index % COLORS.len()],
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let x_axis = || {
+            Axis::default()
+                .bounds([-window, 0.0])
+                .labels([
+                    Span::raw(format!("-{}", format_window(self.window()))),
+                    Span::raw(format!("-{}", format_window(self.window() / 2))),
+                    Span::raw("now"),
+                ])
+                .style(Style::new().dim())
+        };
+
+        let chart = |group: Group, title: &'static str, suffix: &'static str, from_zero: bool| {
+            let datasets: Vec<Dataset> = data
+                .iter()
+                .filter(|((g, _), _, _)| *g == group)
+                .map(|((group, name), points, color)| {
+                    let name = match group {
+                        Group::Power => config.power.name(name),
+                        Group::Thermal => config.thermal.name(name),
+                    };
+                    Dataset::default()
+                        .name(name.to_string())
+                        .marker(symbols::Marker::Braille)
+                        .graph_type(GraphType::Line)
+                        .style(Style::new().fg(*color))
+                        .data(points)
+                })
+                .collect();
+
+            let values = data
+                .iter()
+                .filter(|((g, _), _, _)| *g == group)
+                .flat_map(|(_, points, _)| points.iter().map(|(_, value)| *value));
+            let (min, max) = values.fold((f64::MAX, f64::MIN), |(min, max), value| {
+                (min.min(value), max.max(value))
+            });
+            let (min, max) = match (min <= max, from_zero) {
+                (false, _) => (0.0, 1.0),
+                (true, true) => (0.0, (max * 1.1).max(1.0)),
+                (true, false) => ((min - 5.0).floor(), (max + 5.0).ceil()),
+            };
+
+            Chart::new(datasets)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .title_style(Style::new().bold())
+                        .padding(Padding::horizontal(1)),
+                )
+                .x_axis(x_axis())
+                .y_axis(
+                    Axis::default()
+                        .bounds([min, max])
+                        .labels([
+                            Span::raw(format!("{:.0}{}", min, suffix)),
+                            Span::raw(format!("{:.0}{}", (min + max) / 2.0, suffix)),
+                            Span::raw(format!("{:.0}{}", max, suffix)),
+                        ])
+                        .style(Style::new().dim()),
+                )
+                // The legend on the right lists all the series
+                .hidden_legend_constraints((Constraint::Length(0), Constraint::Length(0)))
+        };
+
+        // Legend
+        let items: Vec<ListItem> = keys
+            .iter()
+            .enumerate()
+            .map(|(index, key @ (group, name))| {
+                let hidden = self.hidden.contains(key);
+                let value = match group {
+                    Group::Power => snapshot
+                        .power
+                        .channels
+                        .iter()
+                        .find(|channel| &channel.name == name)
+                        .map(|channel| format!("{} mW", channel.power)),
+                    Group::Thermal => snapshot
+                        .thermal
+                        .sensors
+                        .iter()
+                        .find(|sensor| &sensor.name == name)
+                        .map(|sensor| unit.format(sensor.temperature)),
+                }
+                .unwrap_or_default();
+                let name = match group {
+                    Group::Power => config.power.name(name),
+                    Group::Thermal => config.thermal.name(name),
+                };
+
+                ListItem::new(Line::from(vec![
+                    Span::raw(if hidden { "[ ] " } else { "[x] " }),
+                    Span::styled("■ ", Style::new().fg(COLORS[index % COLORS.len()])),
+                    Span::raw(format!("{:<16} {:>9}", name, value)),
+                ]))
+            })
+            .collect();
+
+        let legend = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Last {}", format_window(self.window())))
+                    .title_style(Style::new().bold())
+                    .title_bottom(Line::from(" 1-4: window  Space: toggle ").dim()),
+            )
+            .highlight_style(Style::new().reversed());
+
+        let mut state = ListState::default().with_selected(Some(self.selected));
+
+        frame.render_widget(chart(Group::Power, "Power", "mW", true), power_block);
+        frame.render_widget(
+            chart(Group::Thermal, "Thermal", unit.symbol(), false),
+            thermal_block,
+        );
+        frame.render_stateful_widget(legend, legend_block, &mut state);
+    }
+}
+
+/// All the series in the history that are not hidden in the configuration, power rails first
+fn series(history: &History, config: &Config) -> Vec<Key> {
+    let mut power: Vec<&String> = history
+        .power
+        .keys()
+        .filter(|name| !config.power.is_hidden(name))
+        .collect();
+    power.sort();
+    let mut thermal: Vec<&String> = history
+        .thermal
+        .keys()
+        .filter(|name| !config.thermal.is_hidden(name))
+        .collect();
+    thermal.sort();
+
+    power
+        .into_iter()
+        .map(|name| (Group::Power, name.clone()))
+        .chain(
+            thermal
+                .into_iter()
+                .map(|name| (Group::Thermal, name.clone())),
+        )
+        .collect()
+}
+
+fn format_window(seconds: u64) -> String {
+    match seconds {
+        s if s >= 3600 && s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s >= 60 && s % 60 == 0 => format!("{}m", s / 60),
+        s if s >= 60 => format!("{}m{}s", s / 60, s % 60),
+        s => format!("{}s", s),
+    }
+}
@@ -0,0 +1,111 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use clap::{
+    Command, arg, builder::PossibleValuesParser, crate_description, crate_name, crate_version,
+    value_parser,
+};
+use strum::VariantNames;
+
+use crate::ui::Panel;
+
+pub fn cli() -> Command {
+    Command::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .arg(
+            arg!(--root <root>)
+                .env("TEGRATOP_ROOT")
+                .required(false)
+                .global(true)
+                .help("Directory under which /proc, /sys and /etc are read"),
+        )
+        .arg(
+            arg!(--interval <interval>)
+                .required(false)
+                .global(true)
+                .value_parser(value_parser!(u64).range(1..))
+                .help("Sampling interval in milliseconds [default: 1000]"),
+        )
+        .arg(
+            arg!(--config <path>)
+                .required(false)
+                .global(true)
+                .value_parser(value_parser!(PathBuf))
+                .help("Configuration file to read instead of ~/.config/tegratop/config.toml"),
+        )
+        .arg(
+            arg!(--once)
+                .required(false)
+                .global(true)
+                .help("Take a single sample, print or send it and exit"),
+        )
+        .arg(
+            arg!(--"no-color")
+                .required(false)
+                .global(true)
+                .help("Draw the TUI without colors, also enabled by the NO_COLOR env variable"),
+        )
+        .arg(
+            arg!(--"read-only")
+                .required(false)
+                .global(true)
+                .help("Disable the actions that change the system, e.g. for a shared dashboard"),
+        )
+        .arg(
+            arg!(--"log-file" <path>)
+                .required(false)
+                .global(true)
+                .value_parser(value_parser!(PathBuf))
+                .help("Append the logs to a file instead of stderr"),
+        )
+        .arg(
+            arg!(--panels <panels>)
+                .required(false)
+                .value_delimiter(',')
+                .value_parser(PossibleValuesParser::new(Panel::VARIANTS))
+                .help("Comma separated list of the panels to show, in order [default: all]"),
+        )
+        .arg(
+            arg!(--history <samples>)
+                .required(false)
+                .value_parser(value_parser!(u64).range(1..))
+                .help("Number of samples kept for the graphs in the TUI [default: one hour of samples]"),
+        )
+        .arg(
+            arg!(--"log-csv" <path>)
+                .required(false)
+                .conflicts_with("once")
+                .help("Write a CSV row with all the metrics to a file at every refresh"),
+        )
+        .subcommand(
+            Command::new("json").about("Print a single snapshot of all the metrics as JSON and exit"),
+        )
+        .subcommand(
+            Command::new("stream").about("Print one JSON line per sample on stdout until interrupted"),
+        )
+        .subcommand(
+            Command::new("exporter")
+                .about("Serve the metrics for Prometheus on http://<address>/metrics")
+                .arg(arg!(<address>).value_parser(value_parser!(SocketAddr))),
+        )
+        .subcommand(
+            Command::new("textfile")
+                .about("Write the metrics to <dir>/tegratop.prom for the node_exporter textfile collector")
+                .arg(arg!(<dir>).value_parser(value_parser!(PathBuf))),
+        )
+        .subcommand(
+            Command::new("influx")
+                .about("Write the metrics in InfluxDB line protocol to -, udp://<host>:<port> or http://<host>:<port>/<path>?<query>")
+                .arg(arg!(<target>)),
+        )
+        .subcommand(
+            Command::new("record")
+                .about("Record the metrics to a file without the TUI, until interrupted")
+                .arg(arg!(<file>).value_parser(value_parser!(PathBuf))),
+        )
+        .subcommand(
+            Command::new("replay")
+                .about("Replay a recording in the TUI")
+                .arg(arg!(<file>).value_parser(value_parser!(PathBuf))),
+        )
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+use log::error;
+
+use crate::{
+    board::Board, cpu::CPU, disk::Disk, engine::Engine, fan::Fan, gpu::GPU, memory::Memory,
+    network::Network, power::Power, snapshot::Snapshot, system::System, thermal::Thermal,
+};
+
+/// Reads the metrics of a subsystem from procfs/sysfs and exposes them as plain data.
+pub trait Collector: Default {
+    type Snapshot;
+
+    /// Discovers the files to read from.
+    fn init() -> Result<Self>;
+
+    /// Reads the current values, errors are logged.
+    fn refresh(&mut self);
+
+    fn snapshot(&self) -> Self::Snapshot;
+
+    /// Same as `init` but logs the error and falls back to an empty collector.
+    fn new() -> Self {
+        Self::init().unwrap_or_else(|e| {
+            error!("{}", e);
+            Self::default()
+        })
+    }
+}
+
+/// All the collectors of the board.
+#[derive(Debug, Default)]
+pub struct Collectors {
+    pub board: Board,
+    pub cpu: CPU,
+    pub disk: Disk,
+    pub engine: Engine,
+    pub fan: Fan,
+    pub gpu: GPU,
+    pub memory: Memory,
+    pub network: Network,
+    pub power: Power,
+    pub system: System,
+    pub thermal: Thermal,
+}
+
+impl Collector for Collectors {
+    type Snapshot = Snapshot;
+
+    fn init() -> Result<Self> {
+        Ok(Self {
+            board: Board::new(),
+            cpu: CPU::new(),
+            disk: Disk::new(),
+            engine: Engine::new(),
+            fan: Fan::new(),
+            gpu: GPU::new(),
+            memory: Memory::new(),
+            network: Network::new(),
+            power: Power::new(),
+            system: System::new(),
+            thermal: Thermal::new(),
+        })
+    }
+
+    fn refresh(&mut self) {
+        self.cpu.refresh();
+        self.disk.refresh();
+        self.engine.refresh();
+        self.fan.refresh();
+        self.gpu.refresh();
+        self.memory.refresh();
+        self.network.refresh();
+        self.power.refresh();
+        self.system.refresh();
+        self.thermal.refresh();
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            board: self.board.snapshot(),
+            cpu: self.cpu.snapshot(),
+            disk: self.disk.snapshot(),
+            engine: self.engine.snapshot(),
+            fan: self.fan.snapshot(),
+            gpu: self.gpu.snapshot(),
+            memory: self.memory.snapshot(),
+            network: self.network.snapshot(),
+            power: self.power.snapshot(),
+            system: self.system.snapshot(),
+            thermal: self.thermal.snapshot(),
+        }
+    }
+}
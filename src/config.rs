@@ -0,0 +1,267 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use ratatui::style::{Color, Style};
+use serde::Deserialize;
+use toml::Table;
+
+use crate::ui::Panel;
+
+const SYSTEM_CONFIG: &str = "/etc/tegratop.toml";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl TemperatureUnit {
+    pub fn convert(&self, celsius: f32) -> f32 {
+        match self {
+            Self::Celsius => celsius,
+            Self::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            Self::Kelvin => celsius + 273.15,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Celsius => "C",
+            Self::Fahrenheit => "F",
+            Self::Kelvin => "K",
+        }
+    }
+
+    /// Formats a temperature in °C in this unit
+    pub fn format(&self, celsius: f32) -> String {
+        format!("{:.1} {}", self.convert(celsius), self.symbol())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Threshold {
+    pub warning: f64,
+    pub critical: f64,
+}
+
+impl Threshold {
+    pub fn new(warning: f64, critical: f64) -> Self {
+        Self { warning, critical }
+    }
+
+    pub fn level(&self, value: f64) -> Level {
+        if value >= self.critical {
+            Level::Critical
+        } else if value >= self.warning {
+            Level::Warning
+        } else {
+            Level::Normal
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Level {
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Normal => Color::Green,
+            Self::Warning => Color::Yellow,
+            Self::Critical => Color::Red,
+        }
+    }
+
+    /// Style of a bar or a table cell
+    pub fn style(&self) -> Style {
+        Style::new().fg(self.color())
+    }
+
+    /// Style of the borders of a panel, only highlighted above the warning level
+    pub fn border_style(&self) -> Style {
+        match self {
+            Self::Normal => Style::new(),
+            _ => Style::new().fg(self.color()),
+        }
+    }
+}
+
+/// Warning and critical levels of the metrics. Temperatures are in °C and power in mW,
+/// whatever the display unit.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thresholds {
+    /// Utilization of a core in %
+    pub cpu: Threshold,
+    /// Load in %
+    pub gpu: Threshold,
+    /// Used RAM in % of the total
+    pub ram: Threshold,
+    /// Used swap in % of the total
+    pub swap: Threshold,
+    pub temperature: Threshold,
+    /// Per power rail, e.g. `VDD_IN = { warning = 10000, critical = 15000 }`
+    pub power: HashMap<String, Threshold>,
+    pub fan: Option<Threshold>,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            cpu: Threshold::new(70.0, 90.0),
+            gpu: Threshold::new(70.0, 90.0),
+            ram: Threshold::new(80.0, 95.0),
+            swap: Threshold::new(50.0, 80.0),
+            temperature: Threshold::new(70.0, 85.0),
+            power: HashMap::new(),
+            fan: None,
+        }
+    }
+}
+
+/// Display names and hidden entries of the thermal zones or the power rails.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sensors {
+    pub names: HashMap<String, String>,
+    pub hidden: Vec<String>,
+}
+
+impl Sensors {
+    pub fn name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.names.get(name).map(String::as_str).unwrap_or(name)
+    }
+
+    pub fn is_hidden(&self, name: &str) -> bool {
+        self.hidden.iter().any(|hidden| hidden == name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub panels: Option<Vec<Panel>>,
+    /// Refresh interval in milliseconds
+    pub interval: Option<u64>,
+    pub temperature_unit: TemperatureUnit,
+    pub thresholds: Thresholds,
+    pub thermal: Sensors,
+    pub power: Sensors,
+    pub alerts: Vec<AlertConfig>,
+    pub commands: Commands,
+}
+
+/// Programs run by the actions of the TUI
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Commands {
+    /// Switches the power mode
+    pub nvpmodel: String,
+    /// Stops and starts the nvfancontrol service to switch the fan profile
+    pub systemctl: String,
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        Self {
+            nvpmodel: "nvpmodel".to_string(),
+            systemctl: "systemctl".to_string(),
+        }
+    }
+}
+
+/// An `[[alerts]]` table, e.g. `rule = "thermal.CPU > 85 for 30s"`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertConfig {
+    pub rule: String,
+    /// Defaults to the rule
+    pub name: Option<String>,
+    /// Shell command run when the alert fires or resolves
+    pub command: Option<String>,
+    /// File to which a JSON line is appended when the alert fires or resolves
+    pub file: Option<PathBuf>,
+    /// URL to which the JSON line is posted
+    pub webhook: Option<String>,
+}
+
+impl Config {
+    /// Loads `/etc/tegratop.toml` and then the user configuration, which takes precedence.
+    /// `path` replaces the user configuration, it must exist.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut table = Table::new();
+
+        if let Some(system) = read(Path::new(SYSTEM_CONFIG))? {
+            merge(&mut table, system);
+        }
+
+        match path {
+            Some(path) => {
+                let user = read(path)?.context(format!("Failed to find {}", path.display()))?;
+                merge(&mut table, user);
+            }
+            None => {
+                if let Some(user) = user_path().map(|path| read(&path)).transpose()?.flatten() {
+                    merge(&mut table, user);
+                }
+            }
+        }
+
+        let config: Self = table
+            .try_into()
+            .context("Invalid configuration, see the Configuration section of the Readme")?;
+
+        if config.interval == Some(0) {
+            bail!("The interval must be at least 1 millisecond");
+        }
+
+        Ok(config)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/tegratop/config.toml` or `~/.config/tegratop/config.toml`
+fn user_path() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("tegratop").join("config.toml"))
+}
+
+fn read(path: &Path) -> Result<Option<Table>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    let table = content
+        .parse::<Table>()
+        .context(format!("Failed to parse {}", path.display()))?;
+
+    Ok(Some(table))
+}
+
+/// Merges `other` into `base`, the nested tables are merged key by key.
+fn merge(base: &mut Table, other: Table) {
+    for (key, value) in other {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(other)) => merge(base, other),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
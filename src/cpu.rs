@@ -1,18 +1,28 @@
 use anyhow::{Context, Result};
 use log::error;
+#[cfg(feature = "tui")]
+use std::collections::HashMap;
 use std::{
     fs::File,
     io::{Read, Seek},
-    path::{Path, PathBuf},
+    path::PathBuf,
 };
 
+use crate::{collector::Collector, root};
+#[cfg(feature = "tui")]
+use crate::{
+    config::Config,
+    history::{SPARKLINE_WIDTH, Series},
+};
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Style, Stylize},
     text::Line,
-    widgets::{Bar, BarChart, BarGroup, Block, Borders, Padding},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Padding, Paragraph},
 };
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Default)]
 pub struct CPU {
@@ -35,17 +45,21 @@ pub struct CoreFrequency {
     pub value: usize,
 }
 
-impl CPU {
-    pub fn new() -> Self {
-        match CPU::init() {
-            Ok(cpu) => cpu,
-            Err(e) => {
-                error!("{}", e);
-                CPU::default()
-            }
-        }
-    }
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CpuSnapshot {
+    pub cores: Vec<CoreSnapshot>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CoreSnapshot {
+    pub name: String,
+    /// MHz
+    pub frequency: Option<usize>,
+    /// Percentage
+    pub utilization: f64,
+}
 
+impl CPU {
     fn read_frequency(path: &PathBuf) -> Result<CoreFrequency> {
         let mut file = File::open(path)?;
         let mut buffer = String::new();
@@ -59,69 +73,6 @@ impl CPU {
         })
     }
 
-    pub fn init() -> Result<Self> {
-        let mut stat_file = File::open("/proc/stat").context("Failed to open /proc/stat")?;
-        let mut buffer = String::new();
-        stat_file.read_to_string(&mut buffer)?;
-
-        let mut cores: Vec<Core> = Vec::new();
-
-        let mut lines = buffer.lines();
-        lines.next();
-
-        for line in lines {
-            if line.starts_with("cpu") {
-                let fields: Vec<&str> = line.split_whitespace().collect();
-
-                let name: String = fields[0].parse()?;
-
-                let user: usize = fields[1].parse()?;
-                let nice: usize = fields[2].parse()?;
-                let system: usize = fields[3].parse()?;
-                let idle: usize = fields[4].parse()?;
-                let iowait: usize = fields[5].parse()?;
-                let irq: usize = fields[6].parse()?;
-                let softirq: usize = fields[7].parse()?;
-                let steal: usize = fields[8].parse()?;
-                let guest: usize = fields[9].parse()?;
-                let guest_nice: usize = fields[10].parse()?;
-
-                let idle_time = idle + iowait;
-                let systemd_all_time = system + irq + softirq;
-                let virt_all_time = guest + guest_nice;
-                let total_time = user + nice + systemd_all_time + idle_time + steal + virt_all_time;
-
-                let path = Path::new("/sys/devices/system/cpu/")
-                    .join(&name)
-                    .join("cpufreq/cpuinfo_cur_freq");
-
-                let frequency = match CPU::read_frequency(&path) {
-                    Ok(frequency) => Some(frequency),
-                    Err(e) => {
-                        error!("Failed to read from {}", &path.display());
-                        error!("{}", e);
-                        None
-                    }
-                };
-
-                let core = Core {
-                    name,
-                    frequency,
-                    idle_time,
-                    total_time,
-                    utilization: 0.0,
-                };
-
-                cores.push(core);
-            }
-        }
-
-        Ok(Self {
-            stat_file: Some(stat_file),
-            cores,
-        })
-    }
-
     pub fn refresh_frequency(&mut self) -> Result<()> {
         for core in &mut self.cores {
             if let Some(frequency) = &mut core.frequency {
@@ -174,7 +125,11 @@ impl CPU {
                             let total_diff = (total_time - core.total_time) as f64;
                             let idle_diff = (idle_time - core.idle_time) as f64;
 
-                            100.0 * (total_diff - idle_diff) / total_diff
+                            if total_diff > 0.0 {
+                                100.0 * (total_diff - idle_diff) / total_diff
+                            } else {
+                                0.0
+                            }
                         };
 
                         core.total_time = total_time;
@@ -185,8 +140,77 @@ impl CPU {
         }
         Ok(())
     }
+}
+
+impl Collector for CPU {
+    type Snapshot = CpuSnapshot;
+
+    fn init() -> Result<Self> {
+        let stat_path = root::path("/proc/stat");
+        let mut stat_file =
+            File::open(&stat_path).context(format!("Failed to open {}", stat_path.display()))?;
+        let mut buffer = String::new();
+        stat_file.read_to_string(&mut buffer)?;
+
+        let mut cores: Vec<Core> = Vec::new();
+
+        let mut lines = buffer.lines();
+        lines.next();
+
+        for line in lines {
+            if line.starts_with("cpu") {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+
+                let name: String = fields[0].parse()?;
+
+                let user: usize = fields[1].parse()?;
+                let nice: usize = fields[2].parse()?;
+                let system: usize = fields[3].parse()?;
+                let idle: usize = fields[4].parse()?;
+                let iowait: usize = fields[5].parse()?;
+                let irq: usize = fields[6].parse()?;
+                let softirq: usize = fields[7].parse()?;
+                let steal: usize = fields[8].parse()?;
+                let guest: usize = fields[9].parse()?;
+                let guest_nice: usize = fields[10].parse()?;
+
+                let idle_time = idle + iowait;
+                let systemd_all_time = system + irq + softirq;
+                let virt_all_time = guest + guest_nice;
+                let total_time = user + nice + systemd_all_time + idle_time + steal + virt_all_time;
+
+                let path = root::path("/sys/devices/system/cpu/")
+                    .join(&name)
+                    .join("cpufreq/cpuinfo_cur_freq");
+
+                let frequency = match CPU::read_frequency(&path) {
+                    Ok(frequency) => Some(frequency),
+                    Err(e) => {
+                        error!("Failed to read from {}", &path.display());
+                        error!("{}", e);
+                        None
+                    }
+                };
+
+                let core = Core {
+                    name,
+                    frequency,
+                    idle_time,
+                    total_time,
+                    utilization: 0.0,
+                };
+
+                cores.push(core);
+            }
+        }
+
+        Ok(Self {
+            stat_file: Some(stat_file),
+            cores,
+        })
+    }
 
-    pub fn refresh(&mut self) {
+    fn refresh(&mut self) {
         if let Err(e) = self.refresh_utilization() {
             error!("{}", e);
         }
@@ -196,9 +220,42 @@ impl CPU {
         }
     }
 
-    pub fn render(&self, frame: &mut Frame, block: Rect) {
+    fn snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            cores: self
+                .cores
+                .iter()
+                .map(|core| CoreSnapshot {
+                    name: core.name.clone(),
+                    frequency: core.frequency.as_ref().map(|frequency| frequency.value),
+                    utilization: core.utilization,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl CpuSnapshot {
+    pub fn render(
+        &self,
+        frame: &mut Frame,
+        block: Rect,
+        history: &HashMap<String, Series>,
+        config: &Config,
+    ) {
+        let threshold = config.thresholds.cpu;
+
+        let level = self
+            .cores
+            .iter()
+            .map(|core| threshold.level(core.utilization))
+            .max()
+            .unwrap_or_default();
+
         let container = Block::default()
             .borders(Borders::ALL)
+            .border_style(level.border_style())
             .title("CPU")
             .padding(Padding::horizontal(1))
             .title_style(Style::new().bold());
@@ -209,13 +266,49 @@ impl CPU {
             let chunks = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .spacing(2)
                 .split(inside_container);
 
             (chunks[0], chunks[1])
         };
 
+        let (left_block, left_history_block) = {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(24), Constraint::Max(SPARKLINE_WIDTH)])
+                .spacing(1)
+                .split(left_block);
+
+            (chunks[0], chunks[1])
+        };
+
+        let (right_block, right_history_block) = {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(24), Constraint::Max(SPARKLINE_WIDTH)])
+                .spacing(1)
+                .split(right_block);
+
+            (chunks[0], chunks[1])
+        };
+
         let (left_cpu, right_cpu) = self.cores.split_at(self.cores.len() / 2);
 
+        // One line per core, aligned with the bars
+        let sparklines = |cores: &[CoreSnapshot], width: u16| {
+            let lines: Vec<Line> = cores
+                .iter()
+                .flat_map(|core| {
+                    let sparkline = history
+                        .get(&core.name)
+                        .map(|series| series.sparkline(width as usize, 0.0, 100.0))
+                        .unwrap_or_default();
+                    [Line::from(sparkline), Line::default()]
+                })
+                .collect();
+            Paragraph::new(lines)
+        };
+
         let left_cpu_barchat = BarChart::default()
             .block(Block::default())
             .bar_width(1)
@@ -229,12 +322,10 @@ impl CPU {
                             Bar::default()
                                 .label(Line::styled(&core.name, Style::default().bold()))
                                 .value(core.utilization.round() as u64)
-                                .text_value(match &core.frequency {
+                                .style(threshold.level(core.utilization).style())
+                                .text_value(match core.frequency {
                                     Some(frequency) => {
-                                        format!(
-                                            " {}MHz  {:.1}% ",
-                                            frequency.value, core.utilization
-                                        )
+                                        format!(" {}MHz  {:.1}% ", frequency, core.utilization)
                                     }
                                     None => format!("{:.1}% ", core.utilization),
                                 })
@@ -257,12 +348,10 @@ impl CPU {
                             Bar::default()
                                 .label(Line::styled(&core.name, Style::default().bold()))
                                 .value(core.utilization.round() as u64)
-                                .text_value(match &core.frequency {
+                                .style(threshold.level(core.utilization).style())
+                                .text_value(match core.frequency {
                                     Some(frequency) => {
-                                        format!(
-                                            " {}MHz  {:.1}% ",
-                                            frequency.value, core.utilization
-                                        )
+                                        format!(" {}MHz  {:.1}% ", frequency, core.utilization)
                                     }
                                     None => format!("{:.1}% ", core.utilization),
                                 })
@@ -275,5 +364,13 @@ impl CPU {
         frame.render_widget(container, block);
         frame.render_widget(right_cpu_barchat, right_block);
         frame.render_widget(left_cpu_barchat, left_block);
+        frame.render_widget(
+            sparklines(left_cpu, left_history_block.width),
+            left_history_block,
+        );
+        frame.render_widget(
+            sparklines(right_cpu, right_history_block.width),
+            right_history_block,
+        );
     }
 }
@@ -0,0 +1,175 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{engine::HWState, memory::MemSnapshot, snapshot::Snapshot};
+
+/// Appends one CSV row per tick to a file.
+/// The header is built from the cores, rails, sensors and engines found at the first row.
+#[derive(Debug)]
+pub struct CsvLogger {
+    writer: BufWriter<File>,
+    columns: Vec<String>,
+}
+
+impl CsvLogger {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path).context(format!("Failed to create {}", path.display()))?;
+
+        Ok(Self {
+            writer: BufWriter::new(file),
+            columns: Vec::new(),
+        })
+    }
+
+    fn record(snapshot: &Snapshot) -> Result<Vec<(String, String)>> {
+        let mut record: Vec<(String, String)> = Vec::new();
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+        record.push(("timestamp".to_string(), format!("{:.3}", timestamp)));
+
+        for core in &snapshot.cpu.cores {
+            record.push((
+                format!("{}_utilization", core.name),
+                format!("{:.1}", core.utilization),
+            ));
+            record.push((
+                format!("{}_frequency", core.name),
+                core.frequency
+                    .map(|frequency| frequency.to_string())
+                    .unwrap_or_default(),
+            ));
+        }
+
+        record.push((
+            "gpu_load".to_string(),
+            snapshot
+                .gpu
+                .load
+                .map(|load| format!("{:.1}", load))
+                .unwrap_or_default(),
+        ));
+        record.push((
+            "gpu_frequency".to_string(),
+            snapshot
+                .gpu
+                .frequency
+                .map(|frequency| frequency.to_string())
+                .unwrap_or_default(),
+        ));
+
+        // Always present, so a missing first meminfo read does not drop the columns
+        let mem = snapshot.memory.mem.as_ref();
+        let value = |value: fn(&MemSnapshot) -> f64| {
+            mem.map(|mem| value(mem).to_string()).unwrap_or_default()
+        };
+        record.push(("ram_used".to_string(), value(|mem| mem.used_ram)));
+        record.push(("ram_total".to_string(), value(|mem| mem.total_ram)));
+        record.push(("swap_used".to_string(), value(|mem| mem.used_swap)));
+        record.push(("swap_total".to_string(), value(|mem| mem.total_swap)));
+        record.push((
+            "emc_frequency".to_string(),
+            snapshot
+                .memory
+                .emc
+                .as_ref()
+                .map(|emc| emc.frequency.to_string())
+                .unwrap_or_default(),
+        ));
+        record.push((
+            "gpu_shared".to_string(),
+            snapshot
+                .memory
+                .gpu_shared
+                .map(|gpu_shared| gpu_shared.to_string())
+                .unwrap_or_default(),
+        ));
+
+        for channel in &snapshot.power.channels {
+            record.push((format!("power_{}", channel.name), channel.power.to_string()));
+        }
+
+        for sensor in &snapshot.thermal.sensors {
+            record.push((
+                format!("temperature_{}", sensor.name),
+                format!("{:.1}", sensor.temperature),
+            ));
+        }
+
+        for fan in &snapshot.fan.fans {
+            let value = |value: Option<usize>| value.map(|value| value.to_string());
+            record.push((
+                format!("fan_{}_rpm", fan.name),
+                value(fan.rpm).unwrap_or_default(),
+            ));
+            record.push((
+                format!("fan_{}_pwm", fan.name),
+                value(fan.pwm.map(usize::from)).unwrap_or_default(),
+            ));
+            record.push((
+                format!("fan_{}_target_rpm", fan.name),
+                value(fan.target_rpm).unwrap_or_default(),
+            ));
+        }
+
+        for hw in &snapshot.engine.hws {
+            let name = hw.name.to_string().to_lowercase();
+            record.push((
+                format!("{}_running", name),
+                match hw.state {
+                    HWState::Running => "1",
+                    HWState::Idle => "0",
+                }
+                .to_string(),
+            ));
+            record.push((
+                format!("{}_frequency", name),
+                format!("{:.1}", hw.frequency),
+            ));
+        }
+
+        Ok(record)
+    }
+
+    fn escape(field: &str) -> String {
+        if field.contains([',', '"', '\n']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+
+    pub fn write(&mut self, snapshot: &Snapshot) -> Result<()> {
+        let record = CsvLogger::record(snapshot)?;
+
+        if self.columns.is_empty() {
+            self.columns = record.iter().map(|(column, _)| column.clone()).collect();
+
+            let header: Vec<String> = self.columns.iter().map(|c| CsvLogger::escape(c)).collect();
+            writeln!(self.writer, "{}", header.join(","))?;
+        }
+
+        let row: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| {
+                record
+                    .iter()
+                    .find(|(name, _)| name == column)
+                    .map(|(_, value)| CsvLogger::escape(value))
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        writeln!(self.writer, "{}", row.join(","))?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
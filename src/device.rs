@@ -0,0 +1,81 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::root;
+
+/// Accelerator behind a device node, named like the engines
+fn accelerator(device: &Path) -> Option<&'static str> {
+    if device.starts_with("/dev/nvgpu") {
+        return Some("GPU");
+    }
+
+    let name = device.strip_prefix("/dev").ok()?.to_str()?;
+    if name.starts_with("nvidia") {
+        return Some("GPU");
+    }
+
+    let name = name.strip_prefix("nvhost-")?;
+    // nvhost-ctrl is the syncpoint interface, every client opens it
+    let name = name.strip_prefix("ctrl-").unwrap_or(name);
+
+    let accelerator = match name {
+        "ctrl" => return None,
+        // The encoder is MSENC or NVENC depending on the generation
+        _ if name.starts_with("msenc") || name.starts_with("nvenc") => "NVENC",
+        _ if name.starts_with("nvdec") => "NVDEC",
+        _ if name.starts_with("nvjpg") => "NVJPG",
+        _ if name.starts_with("vic") => "VIC",
+        _ if name.contains("nvdla") => "DLA",
+        _ if name.starts_with("pva") => "PVA",
+        _ if name.starts_with("isp") => "ISP",
+        _ if name.starts_with("vi") => "VI",
+        _ if name.ends_with("gpu") => "GPU",
+        _ => return None,
+    };
+
+    Some(accelerator)
+}
+
+/// Accelerators of the device nodes each process has open in `/proc/<pid>/fd`, sorted.
+/// Only the processes of the current user are visible without root.
+pub fn scan() -> HashMap<u32, Vec<&'static str>> {
+    let mut processes = HashMap::new();
+
+    let Ok(entries) = fs::read_dir(root::path("/proc")) else {
+        return processes;
+    };
+
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+
+        let mut accelerators: Vec<&'static str> = fds
+            .flatten()
+            .filter_map(|fd| fs::read_link(fd.path()).ok())
+            .filter_map(|device| accelerator(&device))
+            .collect();
+
+        if !accelerators.is_empty() {
+            accelerators.sort_unstable();
+            accelerators.dedup();
+            processes.insert(pid, accelerators);
+        }
+    }
+
+    processes
+}
+
+/// Name of a process from `/proc/<pid>/comm`
+pub fn command(pid: u32) -> Option<String> {
+    let path = root::path(format!("/proc/{}/comm", pid));
+    fs::read_to_string(path)
+        .ok()
+        .map(|comm| comm.trim().to_string())
+}
@@ -1,13 +1,16 @@
 use anyhow::Context;
 use anyhow::Result;
+use anyhow::bail;
 
 use log::error;
 use std::fs::File;
 use std::io::Read;
 use std::io::Seek;
+use std::time::Instant;
 
-use std::{ffi::CString, fs};
+use std::{ffi::CString, fs, os::unix::ffi::OsStrExt};
 
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
     layout::{Constraint, Rect},
@@ -15,6 +18,9 @@ use ratatui::{
     widgets::{Block, Borders, Padding, Row, Table},
 };
 
+use crate::{collector::Collector, root};
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Default)]
 pub struct Disk {
     device_name: Option<String>,
@@ -26,6 +32,7 @@ pub struct Disk {
 pub struct DiskIO {
     file: File,
     stats: DiskIOStats,
+    last_refresh: Instant,
     total_reads: f64,
     total_writes: f64,
 }
@@ -36,15 +43,32 @@ pub struct DiskIOStats {
     writes: f64,
 }
 
-#[derive(Debug, Default)]
+/// GB
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DiskSpace {
-    total: f64,
-    available: f64,
+    pub total: f64,
+    pub available: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiskSnapshot {
+    pub device_name: Option<String>,
+    pub space: Option<DiskSpace>,
+    pub io: Option<DiskIOSnapshot>,
+}
+
+/// MB/s read and written since the previous refresh
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiskIOSnapshot {
+    pub reads: f64,
+    pub writes: f64,
 }
 
 impl DiskIO {
     fn stats(device_name: &str) -> Result<Self> {
-        let mut file = File::open("/proc/diskstats").context("Failed to open /proc/diskstats")?;
+        let diskstats_path = root::path("/proc/diskstats");
+        let mut file = File::open(&diskstats_path)
+            .context(format!("Failed to open {}", diskstats_path.display()))?;
         let mut buffer = String::new();
         file.read_to_string(&mut buffer)?;
 
@@ -64,6 +88,7 @@ impl DiskIO {
         Ok(Self {
             file,
             stats,
+            last_refresh: Instant::now(),
             total_reads: 0.0,
             total_writes: 0.0,
         })
@@ -88,8 +113,19 @@ impl DiskIO {
             }
         }
 
-        let total_reads = stats.reads - self.stats.reads;
-        let total_writes = stats.writes - self.stats.writes;
+        // The refresh interval is configurable, so the rates use the actual elapsed time
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refresh).as_secs_f64();
+        self.last_refresh = now;
+
+        let (total_reads, total_writes) = if elapsed > 0.0 {
+            (
+                (stats.reads - self.stats.reads) / elapsed,
+                (stats.writes - self.stats.writes) / elapsed,
+            )
+        } else {
+            (0.0, 0.0)
+        };
 
         self.stats = stats;
         self.total_reads = total_reads;
@@ -101,7 +137,7 @@ impl DiskIO {
 
 impl DiskSpace {
     fn stats() -> Result<Option<Self>> {
-        let path = CString::new("/").unwrap();
+        let path = CString::new(root::get().as_os_str().as_bytes())?;
 
         let mut statvfs: libc::statvfs = unsafe { std::mem::zeroed() };
 
@@ -119,54 +155,10 @@ impl DiskSpace {
 }
 
 impl Disk {
-    pub fn new() -> Self {
-        match Disk::root_device_name() {
-            Ok(Some(device_name)) => {
-                let space = match DiskSpace::stats() {
-                    Ok(space) => space,
-                    Err(e) => {
-                        error!("{}", e);
-                        None
-                    }
-                };
-
-                let io = DiskIO::stats(&device_name).map_or_else(
-                    |e| {
-                        error!("{}", e);
-                        None
-                    },
-                    Some,
-                );
-
-                Self {
-                    device_name: Some(device_name),
-                    space,
-                    io,
-                }
-            }
-            Ok(None) => {
-                error!("Can not find the root device name");
-                Self {
-                    device_name: None,
-                    space: None,
-                    io: None,
-                }
-            }
-            Err(e) => {
-                error!("{}", e);
-
-                Self {
-                    device_name: None,
-                    space: None,
-                    io: None,
-                }
-            }
-        }
-    }
-
     pub fn root_device_name() -> Result<Option<String>> {
-        let buffer =
-            fs::read_to_string("/proc/mounts").context("Failed to read from /proc/mounts")?;
+        let mounts_path = root::path("/proc/mounts");
+        let buffer = fs::read_to_string(&mounts_path)
+            .context(format!("Failed to read from {}", mounts_path.display()))?;
         for line in buffer.lines() {
             let parts: Vec<&str> = line.split_whitespace().collect();
             if parts[1] == "/" {
@@ -175,8 +167,40 @@ impl Disk {
         }
         Ok(None)
     }
+}
+
+impl Collector for Disk {
+    type Snapshot = DiskSnapshot;
+
+    fn init() -> Result<Self> {
+        let Some(device_name) = Disk::root_device_name()? else {
+            bail!("Can not find the root device name");
+        };
+
+        let space = match DiskSpace::stats() {
+            Ok(space) => space,
+            Err(e) => {
+                error!("{}", e);
+                None
+            }
+        };
+
+        let io = DiskIO::stats(&device_name).map_or_else(
+            |e| {
+                error!("{}", e);
+                None
+            },
+            Some,
+        );
+
+        Ok(Self {
+            device_name: Some(device_name),
+            space,
+            io,
+        })
+    }
 
-    pub fn refresh(&mut self) {
+    fn refresh(&mut self) {
         if let Ok(stats) = DiskSpace::stats() {
             self.space = stats;
         }
@@ -188,6 +212,20 @@ impl Disk {
         }
     }
 
+    fn snapshot(&self) -> DiskSnapshot {
+        DiskSnapshot {
+            device_name: self.device_name.clone(),
+            space: self.space.clone(),
+            io: self.io.as_ref().map(|io| DiskIOSnapshot {
+                reads: io.total_reads,
+                writes: io.total_writes,
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl DiskSnapshot {
     pub fn render(&self, frame: &mut Frame, block: Rect) {
         let mut rows: Vec<String> = Vec::new();
         let mut space_rows: Vec<String> = Vec::new();
@@ -203,8 +241,8 @@ impl Disk {
 
         if let Some(io) = &self.io {
             io_rows = vec![
-                format!("{:.1}MB/s", io.total_reads),
-                format!("{:.1}MB/s", io.total_writes),
+                format!("{:.1}MB/s", io.reads),
+                format!("{:.1}MB/s", io.writes),
             ];
         }
 
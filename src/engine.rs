@@ -1,16 +1,20 @@
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Style, Stylize},
     widgets::{Block, Borders, Padding, Row, Table},
 };
-use std::fs;
-use std::path::Path;
+use std::{collections::HashMap, fs};
 use strum::IntoEnumIterator;
 use strum_macros::Display;
 use strum_macros::EnumIter;
 
-#[derive(EnumIter, Display, Debug, Default, PartialEq)]
+use crate::{collector::Collector, device, root};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(EnumIter, Display, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub enum HWName {
     #[default]
     APE,
@@ -28,31 +32,61 @@ pub enum HWName {
 #[derive(Debug, Default)]
 pub struct Engine {
     pub hws: Vec<HW>,
+    /// Engines opened by each pid at the last refresh, reused by the process view
+    pub devices: HashMap<u32, Vec<&'static str>>,
+    /// Looks for the processes using the engines. Scanning the open files of every process is
+    /// costly, the TUI only sets it while the Engines panel or the process view is shown.
+    pub scan: bool,
 }
 
-#[derive(Debug, Default, Display)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EngineSnapshot {
+    pub hws: Vec<HW>,
+}
+
+#[derive(Debug, Clone, Default, Display, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum HWState {
     #[default]
     Idle,
     Running,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HW {
     pub name: HWName,
     pub state: HWState,
     pub frequency: f64,
+    /// Processes with the device node of the engine open
+    #[serde(default)]
+    pub processes: Vec<EngineProcess>,
 }
 
-impl Engine {
-    pub fn new() -> Self {
-        Self::default()
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EngineProcess {
+    pub pid: u32,
+    pub command: String,
+}
+
+impl Collector for Engine {
+    type Snapshot = EngineSnapshot;
+
+    fn init() -> Result<Self> {
+        Ok(Self::default())
     }
 
-    pub fn refresh(&mut self) {
+    fn refresh(&mut self) {
         let mut hws: Vec<HW> = Vec::new();
 
-        let stats_path = Path::new("/sys/kernel/debug/clk");
+        self.devices = if self.scan {
+            device::scan()
+        } else {
+            HashMap::new()
+        };
+        let mut holders: Vec<(&u32, &Vec<&str>)> = self.devices.iter().collect();
+        holders.sort_unstable_by_key(|(pid, _)| **pid);
+
+        let stats_path = root::path("/sys/kernel/debug/clk");
 
         for hw_name in HWName::iter() {
             let hw_stats_path = stats_path.join(hw_name.to_string().to_lowercase());
@@ -74,10 +108,24 @@ impl Engine {
                 Err(_) => continue,
             };
 
+            let name = match hw_name {
+                HWName::MSENC => HWName::NVENC.to_string(),
+                _ => hw_name.to_string(),
+            };
+            let processes = holders
+                .iter()
+                .filter(|(_, accelerators)| accelerators.contains(&name.as_str()))
+                .map(|(pid, _)| EngineProcess {
+                    pid: **pid,
+                    command: device::command(**pid).unwrap_or_default(),
+                })
+                .collect();
+
             let hw = HW {
                 name: hw_name,
                 state,
                 frequency,
+                processes,
             };
 
             hws.push(hw);
@@ -86,6 +134,15 @@ impl Engine {
         self.hws = hws;
     }
 
+    fn snapshot(&self) -> EngineSnapshot {
+        EngineSnapshot {
+            hws: self.hws.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl EngineSnapshot {
     pub fn render(&self, frame: &mut Frame, block: Rect) {
         let container = Block::default()
             .borders(Borders::ALL)
@@ -99,6 +156,7 @@ impl Engine {
             let chunks = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .spacing(2)
                 .split(inside_container);
 
             (chunks[0], chunks[1])
@@ -106,42 +164,43 @@ impl Engine {
 
         let (left_engines, right_engines) = self.hws.split_at(self.hws.len() / 2);
 
-        let left_rows: Vec<Row> = left_engines
-            .iter()
-            .map(|hw| {
-                Row::new(vec![hw.name.to_string(), hw.state.to_string(), {
-                    match hw.state {
-                        HWState::Idle => "-".to_string(),
-                        HWState::Running => format!("{:.1} MHz", hw.frequency),
-                    }
-                }])
-            })
-            .collect();
-
-        let right_rows: Vec<Row> = right_engines
-            .iter()
-            .map(|hw| {
-                Row::new(vec![hw.name.to_string(), hw.state.to_string(), {
-                    match hw.state {
-                        HWState::Idle => "-".to_string(),
-                        HWState::Running => format!("{:.1} MHz", hw.frequency),
-                    }
-                }])
-            })
-            .collect();
+        let row = |hw: &HW| {
+            let processes = hw
+                .processes
+                .iter()
+                .map(|process| format!("{} ({})", process.command, process.pid))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            Row::new(vec![
+                hw.name.to_string(),
+                hw.state.to_string(),
+                match hw.state {
+                    HWState::Idle => "-".to_string(),
+                    HWState::Running => format!("{:.1} MHz", hw.frequency),
+                },
+                processes,
+            ])
+        };
+
+        let left_rows: Vec<Row> = left_engines.iter().map(row).collect();
+        let right_rows: Vec<Row> = right_engines.iter().map(row).collect();
 
         let widths = [
             Constraint::Length(7),
             Constraint::Length(8),
             Constraint::Length(10),
+            Constraint::Min(0),
         ];
+        let header =
+            Row::new(vec!["Name", "State", "Frequency", "Processes"]).style(Style::new().bold());
 
         let left_table = Table::new(left_rows, widths)
-            .header(Row::new(vec!["Name", "State", "Frequency"]).style(Style::new().bold()))
+            .header(header.clone())
             .block(Block::default());
 
         let right_table = Table::new(right_rows, widths)
-            .header(Row::new(vec!["Name", "State", "Frequency"]).style(Style::new().bold()))
+            .header(header)
             .block(Block::default());
 
         frame.render_widget(container, block);
@@ -1,202 +1,1017 @@
 use anyhow::Context;
 use log::error;
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use std::{
+    collections::HashMap,
     fs::{self, File},
-    io::{Read, Seek},
+    io::{ErrorKind, Read, Seek},
+    path::{Path, PathBuf},
+    process::{self, Command},
+    sync::{LazyLock, Mutex},
 };
 use strum_macros::Display;
 
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
-    layout::{Constraint, Rect},
-    style::{Style, Stylize},
-    widgets::{Block, Borders, Padding, Row, Table},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    symbols,
+    text::Span,
+    widgets::{Axis, Block, Borders, Cell, Chart, Dataset, GraphType, Padding, Row, Table},
 };
 
+#[cfg(feature = "tui")]
+use crate::config::Config;
+use crate::{collector::Collector, device, root};
+use serde::{Deserialize, Serialize};
+
+const CONF: &str = "/etc/nvfancontrol.conf";
+/// Saved by nvfancontrol when it stops, it takes precedence over the configuration
+const STATUS: &str = "/var/lib/nvfancontrol/status";
+/// Manual mode of `pwm1_enable` in the hwmon ABI, 0 is full speed and 2 and above automatic
+const MANUAL: u8 = 1;
+
+/// `pwm1_enable` files switched to manual by `set_pwm`, with the mode to put back
+static SAVED_ENABLE: LazyLock<Mutex<HashMap<PathBuf, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
 #[derive(Debug, Default)]
 pub struct Fan {
-    rpm: Option<FanRPM>,
-    profile: Option<FanProfile>,
+    pub fans: Vec<FanDevice>,
+    /// nvfancontrol.conf
+    conf: Option<File>,
+    /// pid of nvfancontrol while it runs
+    nvfancontrol: Option<u32>,
 }
 
+/// A fan of the hwmon directories and its `<FAN n>` block of nvfancontrol.conf, in the same order
 #[derive(Debug)]
-struct FanRPM {
-    file: File,
-    value: usize,
+pub struct FanDevice {
+    pub name: String,
+    pub rpm: Option<FanRPM>,
+    pub pwm: Option<FanPWM>,
+    pub profile: Option<FanProfile>,
 }
 
 #[derive(Debug)]
-struct FanProfile {
+pub struct FanRPM {
     file: File,
-    value: Profile,
+    pub value: usize,
 }
 
-#[derive(Debug, Display)]
-enum Profile {
-    Quiet,
-    Cool,
-    Unknown,
+/// `pwm1` and `pwm1_enable` of the fan hwmon
+#[derive(Debug)]
+pub struct FanPWM {
+    dir: PathBuf,
+    /// 0 to 255
+    pub value: u8,
+    /// None when the driver has no `pwm1_enable`
+    pub enable: Option<u8>,
 }
 
-impl FanRPM {
-    fn new() -> Result<Option<Self>> {
-        let entries = fs::read_dir("/sys/class/hwmon/")
-            .context("Failed to read from the directory /sys/class/hwmon")?;
-
-        for entry in entries {
-            let entry = entry?;
-            let entry_path = entry.path();
-
-            if entry_path.join("rpm").exists() {
-                let rpm_file_path = entry_path.join("rpm");
-                let mut file = File::open(&rpm_file_path)
-                    .context(format!("Failed to read from {}", &rpm_file_path.display()))?;
-                let mut buffer = String::new();
-                file.read_to_string(&mut buffer)?;
-
-                let fan_rpm = buffer.trim().parse::<usize>()?;
-
-                return Ok(Some(FanRPM {
-                    file,
-                    value: fan_rpm,
-                }));
+#[derive(Debug)]
+pub struct FanProfile {
+    /// From the status of nvfancontrol, or the default of the configuration
+    pub name: String,
+    /// Names of the `FAN_PROFILE` tables
+    pub available: Vec<String>,
+    pub curves: Vec<(String, Vec<CurvePoint>)>,
+    /// `temp` files of the zones of the thermal group
+    zones: Vec<PathBuf>,
+    /// °C, the hottest zone of the thermal group
+    pub temperature: Option<f32>,
+}
+
+/// Who sets the PWM duty: the driver when `pwm1_enable` is 2 or above, nvfancontrol while it runs
+/// with a profile for the fan, else the last written duty stays. On the pwm-fan driver of Jetson
+/// `pwm1_enable` is 1 by default, so only nvfancontrol makes it automatic.
+#[derive(Debug, Clone, Copy, PartialEq, Display, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Control {
+    Auto,
+    Manual,
+}
+
+/// A row of a `FAN_PROFILE` table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurvePoint {
+    /// °C
+    pub temperature: f32,
+    /// 0 to 255
+    pub pwm: u8,
+    /// Older configurations have no RPM column
+    pub rpm: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FanSnapshot {
+    #[serde(default)]
+    pub fans: Vec<FanDeviceSnapshot>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FanDeviceSnapshot {
+    pub name: String,
+    pub rpm: Option<usize>,
+    /// 0 to 255
+    pub pwm: Option<u8>,
+    pub control: Option<Control>,
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub available_profiles: Vec<String>,
+    /// From the curve of the profile at the current temperature
+    pub target_rpm: Option<usize>,
+    /// °C, the hottest zone of the thermal group of nvfancontrol
+    pub temperature: Option<f32>,
+    /// Table of the current profile
+    #[serde(default)]
+    pub curve: Vec<CurvePoint>,
+}
+
+impl FanSnapshot {
+    /// The fastest fan
+    pub fn rpm(&self) -> Option<usize> {
+        self.fans.iter().filter_map(|fan| fan.rpm).max()
+    }
+}
+
+/// Files of a fan in the hwmon directories
+#[derive(Debug)]
+struct Hwmon {
+    name: String,
+    /// `rpm` or `fan1_input`
+    rpm: Option<PathBuf>,
+    /// Directory with `pwm1`
+    pwm: Option<PathBuf>,
+}
+
+/// Finds the fans of the hwmon directories, in the order of the directories. The PWM and the
+/// tachometer of a fan are in the same directory or in two, e.g. `pwmfan` and `tachometer`,
+/// which are then paired in order. Names shared by several fans get their index.
+fn discover() -> Result<Vec<Hwmon>> {
+    let hwmon_path = root::path("/sys/class/hwmon/");
+    let entries = fs::read_dir(&hwmon_path).context(format!(
+        "Failed to read from the directory {}",
+        hwmon_path.display()
+    ))?;
+
+    let mut dirs: Vec<(usize, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let index = path
+                .file_name()?
+                .to_str()?
+                .strip_prefix("hwmon")?
+                .parse::<usize>()
+                .ok()?;
+            Some((index, path))
+        })
+        .collect();
+    dirs.sort();
+
+    let mut fans = Vec::new();
+    let mut pwms = Vec::new();
+    let mut tachometers = Vec::new();
+
+    for (_, dir) in dirs {
+        let name = fs::read_to_string(dir.join("name"))
+            .map(|name| name.trim().to_string())
+            .unwrap_or_default();
+        let rpm = ["rpm", "fan1_input"]
+            .iter()
+            .map(|file| dir.join(file))
+            .find(|path| path.exists());
+        let pwm = dir.join("pwm1").exists().then(|| dir.clone());
+
+        match (rpm, pwm) {
+            (Some(rpm), Some(pwm)) => fans.push(Hwmon {
+                name,
+                rpm: Some(rpm),
+                pwm: Some(pwm),
+            }),
+            (Some(rpm), None) => tachometers.push((name, rpm)),
+            (None, Some(pwm)) => pwms.push((name, pwm)),
+            (None, None) => {}
+        }
+    }
+
+    let mut tachometers = tachometers.into_iter();
+    for (name, pwm) in pwms {
+        fans.push(Hwmon {
+            name,
+            rpm: tachometers.next().map(|(_, rpm)| rpm),
+            pwm: Some(pwm),
+        });
+    }
+    fans.extend(tachometers.map(|(name, rpm)| Hwmon {
+        name,
+        rpm: Some(rpm),
+        pwm: None,
+    }));
+
+    let names: Vec<String> = fans.iter().map(|fan| fan.name.clone()).collect();
+    for (index, fan) in fans.iter_mut().enumerate() {
+        if fan.name.is_empty() {
+            fan.name = format!("fan{}", index + 1);
+        } else if names.iter().filter(|name| **name == fan.name).count() > 1 {
+            let rank = names[..index]
+                .iter()
+                .filter(|name| **name == fan.name)
+                .count();
+            fan.name = format!("{}{}", fan.name, rank);
+        }
+    }
+
+    Ok(fans)
+}
+
+/// What tegratop uses from a `<FAN n>` block of nvfancontrol.conf
+#[derive(Debug, Default)]
+struct ConfFan {
+    default_profile: Option<String>,
+    curves: Vec<(String, Vec<CurvePoint>)>,
+    /// Thermal zones of the `THERMAL_GROUP` blocks, e.g. `CPU-therm`
+    zones: Vec<String>,
+}
+
+impl ConfFan {
+    fn is_empty(&self) -> bool {
+        self.default_profile.is_none() && self.curves.is_empty() && self.zones.is_empty()
+    }
+}
+
+/// Parses the blocks of nvfancontrol.conf, one `<FAN n>` block by fan:
+/// ```text
+/// <FAN 1>
+///     FAN_PROFILE quiet {
+///         #TEMP HYST PWM RPM
+///         30    11   66  1550
+///     }
+///     THERMAL_GROUP 0 {
+///         GROUP_MAX_TEMP 105
+///         CPU-therm 20,2,1 0
+///     }
+///     FAN_DEFAULT_PROFILE quiet
+/// ```
+/// Older configurations have no `<FAN n>` line, they are read as a single fan.
+fn parse_conf(conf: &str) -> Vec<ConfFan> {
+    let mut fans: Vec<ConfFan> = Vec::new();
+    let mut blocks: Vec<(String, String)> = Vec::new();
+    // Index of the table being read in the curves of the fan
+    let mut curve: Option<usize> = None;
+    let mut headers = 0;
+
+    for line in conf.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        if line.starts_with("<FAN") {
+            // The global settings before the first fan, e.g. POLLING_INTERVAL, are not a fan
+            if !(fans.len() == 1 && headers == 0 && fans[0].is_empty()) {
+                fans.push(ConfFan::default());
             }
+            headers += 1;
+            continue;
+        }
+        if line.is_empty() {
+            continue;
         }
+        if fans.is_empty() {
+            fans.push(ConfFan::default());
+        }
+        let Some(fan) = fans.last_mut() else {
+            continue;
+        };
 
-        Ok(None)
+        if line.ends_with('{') {
+            let name = |index: usize| {
+                parts
+                    .get(index)
+                    .filter(|part| **part != "{")
+                    .unwrap_or(&"")
+                    .to_string()
+            };
+            let (kind, name) = (name(0), name(1));
+            if kind == "FAN_PROFILE" && !fan.curves.iter().any(|(profile, _)| *profile == name) {
+                curve = Some(fan.curves.len());
+                fan.curves.push((name.clone(), Vec::new()));
+            }
+            blocks.push((kind, name));
+            continue;
+        }
+        if line == "}" {
+            blocks.pop();
+            curve = None;
+            continue;
+        }
+
+        match (blocks.last(), parts.as_slice()) {
+            (Some((kind, _)), [temperature, _, pwm, rest @ ..]) if kind == "FAN_PROFILE" => {
+                let (Some(index), Ok(temperature), Ok(pwm)) =
+                    (curve, temperature.parse::<f32>(), pwm.parse::<u8>())
+                else {
+                    continue;
+                };
+                fan.curves[index].1.push(CurvePoint {
+                    temperature,
+                    pwm,
+                    rpm: rest.first().and_then(|rpm| rpm.parse::<usize>().ok()),
+                });
+            }
+            (Some((kind, _)), [zone, ..])
+                if kind == "THERMAL_GROUP"
+                    && *zone != "GROUP_MAX_TEMP"
+                    && !fan.zones.iter().any(|z| z == zone) =>
+            {
+                fan.zones.push(zone.to_string())
+            }
+            (_, ["FAN_DEFAULT_PROFILE", name]) => fan.default_profile = Some(name.to_string()),
+            _ => {}
+        }
     }
 
-    fn refresh(&mut self) -> Result<()> {
-        self.file.seek(std::io::SeekFrom::Start(0))?;
-        let mut buffer = String::new();
-        self.file.read_to_string(&mut buffer)?;
-        let fan_rpm = buffer.trim().parse::<usize>()?;
-        self.value = fan_rpm;
-        Ok(())
+    fans
+}
+
+/// The profile nvfancontrol runs for each fan, by index, from lines like `FAN1:FAN_PROFILE:cool`
+fn read_status() -> HashMap<usize, String> {
+    let Ok(status) = fs::read_to_string(root::path(STATUS)) else {
+        return HashMap::new();
+    };
+
+    status
+        .lines()
+        .filter_map(|line| {
+            let tokens: Vec<&str> = line
+                .split(|c: char| c == ':' || c.is_whitespace())
+                .filter(|token| !token.is_empty())
+                .collect();
+            let position = tokens.iter().position(|token| *token == "FAN_PROFILE")?;
+            let profile = tokens.get(position + 1)?;
+            let index = match position {
+                0 => 0,
+                _ => tokens[position - 1]
+                    .strip_prefix("FAN")?
+                    .parse::<usize>()
+                    .ok()?
+                    .checked_sub(1)?,
+            };
+            Some((index, profile.to_string()))
+        })
+        .collect()
+}
+
+/// `temp` files of the thermal zones, the names of nvfancontrol.conf and of the kernel differ,
+/// e.g. `CPU-therm` and `cpu-thermal`
+fn zone_paths(zones: &[String]) -> Vec<PathBuf> {
+    let normalize = |name: &str| {
+        let name = name.trim().to_lowercase();
+        let name = name.strip_suffix("-thermal").unwrap_or(&name);
+        name.strip_suffix("-therm").unwrap_or(name).to_string()
+    };
+    let zones: Vec<String> = zones.iter().map(|zone| normalize(zone)).collect();
+
+    let Ok(entries) = fs::read_dir(root::path("/sys/devices/virtual/thermal/")) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            fs::read_to_string(path.join("type"))
+                .is_ok_and(|name| zones.contains(&normalize(&name)))
+        })
+        .map(|path| path.join("temp"))
+        .collect()
+}
+
+/// Value of the curve at a temperature, interpolated between the rows of the table
+fn interpolate(
+    curve: &[CurvePoint],
+    temperature: f32,
+    value: impl Fn(&CurvePoint) -> Option<f32>,
+) -> Option<f32> {
+    let first = curve.first()?;
+    if temperature <= first.temperature {
+        return value(first);
     }
+
+    for points in curve.windows(2) {
+        let (low, high) = (&points[0], &points[1]);
+        // Two rows at the same temperature are a step, the upper one applies
+        if temperature <= high.temperature && high.temperature <= low.temperature {
+            return value(high);
+        }
+        if temperature <= high.temperature {
+            let ratio = (temperature - low.temperature) / (high.temperature - low.temperature);
+            let (low, high) = (value(low)?, value(high)?);
+            return Some(low + (high - low) * ratio);
+        }
+    }
+
+    value(curve.last()?)
 }
 
-impl FanProfile {
-    fn new() -> Result<Option<Self>> {
-        let mut file = File::open("/etc/nvfancontrol.conf")
-            .context("Faile to read from /etc/nvfancontrol.conf")?;
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer)?;
+/// Switches nvfancontrol to other profiles, by fan index, `None` keeps the default of a fan.
+/// The service is stopped, since it saves its status when it stops, then the default profiles
+/// of the configuration are rewritten and the status removed before the service starts again.
+/// The profiles the fans run are passed, not only the new one, so that none of them changes
+/// when the status is removed.
+pub fn set_profiles(systemctl: &str, profiles: &[Option<String>]) -> Result<()> {
+    service(systemctl, "stop")?;
+    let written = write_profiles(profiles);
+    // Started again even when the configuration could not be written
+    service(systemctl, "start")?;
+    written
+}
+
+/// Runs `<systemctl> <action> nvfancontrol`
+fn service(systemctl: &str, action: &str) -> Result<()> {
+    let output = Command::new(systemctl)
+        .arg(action)
+        .arg("nvfancontrol")
+        .output()
+        .context(format!("Failed to run {}", systemctl))?;
+
+    if !output.status.success() {
+        let text = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        bail!(
+            "{} {} nvfancontrol failed: {}",
+            systemctl,
+            action,
+            text.lines().last().unwrap_or_default().trim()
+        );
+    }
+
+    Ok(())
+}
+
+fn write_profiles(profiles: &[Option<String>]) -> Result<()> {
+    let path = root::path(CONF);
+    let conf =
+        fs::read_to_string(&path).context(format!("Failed to read from {}", path.display()))?;
 
-        for line in buffer.lines() {
+    let mut found = false;
+    // Same counting as parse_conf, the lines before the first `<FAN n>` belong to the first fan
+    let mut fan: Option<usize> = None;
+    let conf: String = conf
+        .lines()
+        .map(|line| {
             let parts: Vec<&str> = line.split_whitespace().collect();
-            if let ["FAN_DEFAULT_PROFILE", mode] = parts.as_slice() {
-                match *mode {
-                    "quiet" => {
-                        return Ok(Some(FanProfile {
-                            file,
-                            value: Profile::Quiet,
-                        }));
-                    }
-                    "cool" => {
-                        return Ok(Some(FanProfile {
-                            file,
-                            value: Profile::Cool,
-                        }));
-                    }
-                    _ => {
-                        return Ok(Some(FanProfile {
-                            file,
-                            value: Profile::Unknown,
-                        }));
-                    }
+            if line.trim_start().starts_with("<FAN") {
+                fan = Some(fan.map_or(0, |fan| fan + 1));
+            }
+            match (parts.as_slice(), profiles.get(fan.unwrap_or_default())) {
+                (["FAN_DEFAULT_PROFILE", _], Some(Some(profile))) => {
+                    found = true;
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    format!("{}FAN_DEFAULT_PROFILE {}\n", indent, profile)
                 }
+                _ => format!("{}\n", line),
             }
+        })
+        .collect();
+
+    if !found {
+        bail!("No FAN_DEFAULT_PROFILE in {}", path.display());
+    }
+
+    // Written next to the configuration and renamed, so a failed write never leaves it truncated
+    let tmp_path = path.with_extension(format!("conf.{}", process::id()));
+    fs::write(&tmp_path, conf).context(format!("Failed to write to {}", tmp_path.display()))?;
+    if let Ok(metadata) = fs::metadata(&path) {
+        fs::set_permissions(&tmp_path, metadata.permissions()).context(format!(
+            "Failed to set the permissions of {}",
+            tmp_path.display()
+        ))?;
+    }
+    fs::rename(&tmp_path, &path).context(format!(
+        "Failed to rename {} to {}",
+        tmp_path.display(),
+        path.display()
+    ))?;
+
+    let status = root::path(STATUS);
+    match fs::remove_file(&status) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(e).context(format!("Failed to remove {}", status.display()))
         }
+        _ => Ok(()),
+    }
+}
+
+/// pid of nvfancontrol when it runs, from the names of the processes
+fn nvfancontrol_pid() -> Option<u32> {
+    fs::read_dir(root::path("/proc"))
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .find(|pid| device::command(*pid).as_deref() == Some("nvfancontrol"))
+}
 
-        Ok(None)
+pub fn nvfancontrol_running() -> bool {
+    nvfancontrol_pid().is_some()
+}
+
+/// Sets the PWM duty of a fan, 0 to 255. nvfancontrol is the automatic control on Jetson, it
+/// writes `pwm1` at every poll so it is stopped first. A driver in another mode than manual is
+/// switched to manual, otherwise it ignores `pwm1`. `set_auto` undoes both.
+pub fn set_pwm(systemctl: &str, fan: &str, duty: u8) -> Result<()> {
+    let Some(dir) = discover()?
+        .into_iter()
+        .find(|hwmon| hwmon.name == fan)
+        .and_then(|hwmon| hwmon.pwm)
+    else {
+        bail!("The fan {} has no PWM", fan);
+    };
+
+    if nvfancontrol_running() {
+        service(systemctl, "stop")?;
+    }
+
+    let enable = dir.join("pwm1_enable");
+    if let Ok(mode) = fs::read_to_string(&enable)
+        && mode.trim() != MANUAL.to_string()
+    {
+        fs::write(&enable, MANUAL.to_string())
+            .context(format!("Failed to write to {}", enable.display()))?;
+        // Kept from the first manual duty, the later ones find the manual mode
+        SAVED_ENABLE
+            .lock()
+            .unwrap()
+            .entry(enable)
+            .or_insert(mode.trim().to_string());
+    }
+
+    let pwm = dir.join("pwm1");
+    fs::write(&pwm, duty.to_string()).context(format!("Failed to write to {}", pwm.display()))
+}
+
+/// Puts back the `pwm1_enable` modes changed by `set_pwm` and starts nvfancontrol when it is
+/// configured
+pub fn set_auto(systemctl: &str) -> Result<()> {
+    for (enable, mode) in SAVED_ENABLE.lock().unwrap().drain() {
+        fs::write(&enable, mode).context(format!("Failed to write to {}", enable.display()))?;
+    }
+
+    if root::path(CONF).exists() {
+        service(systemctl, "start")?;
+    }
+
+    Ok(())
+}
+
+impl FanRPM {
+    fn new(path: &Path) -> Result<Self> {
+        let file = File::open(path).context(format!("Failed to read from {}", path.display()))?;
+
+        let mut rpm = FanRPM { file, value: 0 };
+        rpm.refresh()?;
+
+        Ok(rpm)
     }
 
     fn refresh(&mut self) -> Result<()> {
         self.file.seek(std::io::SeekFrom::Start(0))?;
         let mut buffer = String::new();
         self.file.read_to_string(&mut buffer)?;
+        let fan_rpm = buffer.trim().parse::<usize>()?;
+        self.value = fan_rpm;
+        Ok(())
+    }
+}
+
+impl FanPWM {
+    fn new(dir: PathBuf) -> Result<Self> {
+        let mut pwm = FanPWM {
+            dir,
+            value: 0,
+            enable: None,
+        };
+        pwm.refresh()?;
+
+        Ok(pwm)
+    }
+
+    /// The files are written by tegratop too, they are read again each time
+    fn refresh(&mut self) -> Result<()> {
+        let pwm = self.dir.join("pwm1");
+        self.value = fs::read_to_string(&pwm)
+            .context(format!("Failed to read from {}", pwm.display()))?
+            .trim()
+            .parse::<u8>()
+            .context(format!("Invalid PWM in {}", pwm.display()))?;
+
+        self.enable = fs::read_to_string(self.dir.join("pwm1_enable"))
+            .ok()
+            .and_then(|enable| enable.trim().parse::<u8>().ok());
 
-        for line in buffer.lines() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if let ["FAN_DEFAULT_PROFILE", mode] = parts.as_slice() {
-                match *mode {
-                    "quiet" => {
-                        self.value = Profile::Quiet;
-                    }
-                    "cool" => {
-                        self.value = Profile::Cool;
-                    }
-                    _ => {
-                        self.value = Profile::Unknown;
-                    }
-                }
-            }
-        }
         Ok(())
     }
 }
 
+impl FanProfile {
+    fn new(conf: ConfFan, status: Option<String>) -> Option<Self> {
+        let mut profile = FanProfile {
+            name: String::new(),
+            available: Vec::new(),
+            curves: Vec::new(),
+            zones: zone_paths(&conf.zones),
+            temperature: None,
+        };
+        profile.update(conf, status);
+
+        (!profile.name.is_empty()).then_some(profile)
+    }
+
+    /// nvfancontrol can switch the profile at runtime, the configuration only has the default
+    fn update(&mut self, conf: ConfFan, status: Option<String>) {
+        if let Some(name) = status.or(conf.default_profile) {
+            self.name = name;
+        }
+        self.available = conf.curves.iter().map(|(name, _)| name.clone()).collect();
+        self.curves = conf.curves;
+
+        self.temperature = self
+            .zones
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok()?.trim().parse::<f32>().ok())
+            .map(|temperature| temperature / 1000.0)
+            .reduce(f32::max);
+    }
+
+    /// Table of the current profile
+    fn curve(&self) -> Vec<CurvePoint> {
+        self.curves
+            .iter()
+            .find(|(name, _)| *name == self.name)
+            .map(|(_, curve)| curve.clone())
+            .unwrap_or_default()
+    }
+}
+
 impl Fan {
-    pub fn new() -> Self {
-        let rpm = match FanRPM::new() {
-            Ok(rpm) => rpm,
+    fn read_conf(&mut self) -> Result<Vec<ConfFan>> {
+        let Some(file) = &mut self.conf else {
+            return Ok(Vec::new());
+        };
+        file.seek(std::io::SeekFrom::Start(0))?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer)?;
+
+        Ok(parse_conf(&buffer))
+    }
+}
+
+impl Collector for Fan {
+    type Snapshot = FanSnapshot;
+
+    fn init() -> Result<Self> {
+        let hwmons = match discover() {
+            Ok(hwmons) => hwmons,
             Err(e) => {
                 error!("{}", e);
+                Vec::new()
+            }
+        };
+
+        let conf_path = root::path(CONF);
+        let conf = match File::open(&conf_path) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                error!("Failed to read from {}: {}", conf_path.display(), e);
                 None
             }
         };
 
-        let profile = match FanProfile::new() {
-            Ok(profile) => profile,
+        let mut fan = Self {
+            fans: Vec::new(),
+            conf,
+            nvfancontrol: nvfancontrol_pid(),
+        };
+        let conf = match fan.read_conf() {
+            Ok(conf) => conf,
             Err(e) => {
                 error!("{}", e);
-                None
+                Vec::new()
             }
         };
+        let mut status = read_status();
+
+        // A fan of the configuration may have no hwmon, e.g. when the driver is not loaded
+        let mut hwmons = hwmons.into_iter();
+        let mut conf = conf.into_iter();
+        for index in 0.. {
+            let (hwmon, conf) = match (hwmons.next(), conf.next()) {
+                (None, None) => break,
+                next => next,
+            };
 
-        Self { rpm, profile }
+            let rpm = hwmon
+                .as_ref()
+                .and_then(|hwmon| hwmon.rpm.as_deref())
+                .and_then(|path| match FanRPM::new(path) {
+                    Ok(rpm) => Some(rpm),
+                    Err(e) => {
+                        error!("{}", e);
+                        None
+                    }
+                });
+            let pwm = hwmon
+                .as_ref()
+                .and_then(|hwmon| hwmon.pwm.clone())
+                .and_then(|dir| match FanPWM::new(dir) {
+                    Ok(pwm) => Some(pwm),
+                    Err(e) => {
+                        error!("{}", e);
+                        None
+                    }
+                });
+
+            fan.fans.push(FanDevice {
+                name: hwmon.map_or(format!("fan{}", index + 1), |hwmon| hwmon.name),
+                rpm,
+                pwm,
+                profile: conf.and_then(|conf| FanProfile::new(conf, status.remove(&index))),
+            });
+        }
+
+        Ok(fan)
     }
 
-    pub fn refresh(&mut self) {
-        if let Some(rpm) = &mut self.rpm
-            && let Err(e) = rpm.refresh()
-        {
-            error!("{}", e);
+    fn refresh(&mut self) {
+        // Only the known pid is read again while nvfancontrol keeps running
+        self.nvfancontrol = self
+            .nvfancontrol
+            .filter(|pid| device::command(*pid).as_deref() == Some("nvfancontrol"))
+            .or_else(nvfancontrol_pid);
+
+        let conf = match self.read_conf() {
+            Ok(conf) => conf,
+            Err(e) => {
+                error!("{}", e);
+                Vec::new()
+            }
+        };
+        let mut status = read_status();
+
+        let mut conf = conf.into_iter();
+        for (index, fan) in self.fans.iter_mut().enumerate() {
+            let conf = conf.next();
+            if let Some(rpm) = &mut fan.rpm
+                && let Err(e) = rpm.refresh()
+            {
+                error!("{}", e);
+            }
+            if let Some(pwm) = &mut fan.pwm
+                && let Err(e) = pwm.refresh()
+            {
+                error!("{}", e);
+            }
+            if let Some(profile) = &mut fan.profile
+                && let Some(conf) = conf
+            {
+                profile.update(conf, status.remove(&index));
+            }
         }
-        if let Some(profile) = &mut self.profile
-            && let Err(e) = profile.refresh()
-        {
-            error!("{}", e);
+    }
+
+    fn snapshot(&self) -> FanSnapshot {
+        FanSnapshot {
+            fans: self
+                .fans
+                .iter()
+                .map(|fan| fan.snapshot(self.nvfancontrol.is_some()))
+                .collect(),
         }
     }
+}
 
-    pub fn render(&self, frame: &mut Frame, block: Rect) {
-        let rows: [Row; 1] = [Row::new(vec![
-            match &self.profile {
-                Some(profile) => profile.value.to_string().to_lowercase(),
-                None => " - ".to_string(),
-            },
-            match &self.rpm {
-                Some(rpm) => rpm.value.to_string(),
-                None => " - ".to_string(),
+impl FanDevice {
+    fn snapshot(&self, nvfancontrol: bool) -> FanDeviceSnapshot {
+        let profile = self.profile.as_ref();
+        let curve = profile.map(FanProfile::curve).unwrap_or_default();
+        let temperature = profile.and_then(|profile| profile.temperature);
+
+        FanDeviceSnapshot {
+            name: self.name.clone(),
+            rpm: self.rpm.as_ref().map(|rpm| rpm.value),
+            pwm: self.pwm.as_ref().map(|pwm| pwm.value),
+            control: match (&self.pwm, profile) {
+                (Some(pwm), _) if pwm.enable.is_some_and(|enable| enable > MANUAL) => {
+                    Some(Control::Auto)
+                }
+                (_, Some(_)) if nvfancontrol => Some(Control::Auto),
+                (Some(_), _) => Some(Control::Manual),
+                _ => None,
             },
-        ])];
-
-        let widths = [Constraint::Length(10), Constraint::Fill(1)];
-
-        let fan = Table::new(rows, widths)
-            .header(Row::new(vec!["Profile", "RPM"]).style(Style::new().bold()))
-            .block(
-                Block::default()
-                    .title("Fan")
-                    .title_style(Style::new().bold())
-                    .padding(Padding::horizontal(1))
-                    .borders(Borders::ALL),
+            profile: profile.map(|profile| profile.name.clone()),
+            available_profiles: profile
+                .map(|profile| profile.available.clone())
+                .unwrap_or_default(),
+            target_rpm: temperature
+                .and_then(|t| interpolate(&curve, t, |point| point.rpm.map(|rpm| rpm as f32)))
+                .map(|rpm| rpm.round() as usize),
+            temperature,
+            curve,
+        }
+    }
+}
+
+/// Percent of a 0 to 255 PWM
+#[cfg(feature = "tui")]
+fn duty(pwm: u8) -> f32 {
+    pwm as f32 * 100.0 / 255.0
+}
+
+/// Colors of the curves, the name of the fan has the color of its curve
+#[cfg(feature = "tui")]
+const COLORS: [Color; 4] = [Color::Cyan, Color::Magenta, Color::Blue, Color::LightGreen];
+
+#[cfg(feature = "tui")]
+impl FanSnapshot {
+    pub fn render(&self, frame: &mut Frame, block: Rect, config: &Config) {
+        let level = |fan: &FanDeviceSnapshot| match (fan.rpm, config.thresholds.fan) {
+            (Some(rpm), Some(threshold)) => Some(threshold.level(rpm as f64)),
+            _ => None,
+        };
+
+        let text = |value: Option<String>| value.unwrap_or(" - ".to_string());
+
+        let rows: Vec<Row> = self
+            .fans
+            .iter()
+            .enumerate()
+            .map(|(index, fan)| {
+                let name = Cell::new(fan.name.clone());
+                Row::new(vec![
+                    match fan.curve.is_empty() {
+                        true => name,
+                        false => name.style(Style::new().fg(COLORS[index % COLORS.len()])),
+                    },
+                    Cell::new(text(fan.profile.clone())),
+                    Cell::new(text(fan.control.map(|control| control.to_string()))),
+                    Cell::new(text(fan.pwm.map(|pwm| format!("{:.0}%", duty(pwm))))),
+                    match (&fan.rpm, level(fan)) {
+                        (Some(rpm), Some(level)) => Cell::new(rpm.to_string()).style(level.style()),
+                        (Some(rpm), None) => Cell::new(rpm.to_string()),
+                        (None, _) => Cell::new(" - "),
+                    },
+                    Cell::new(text(fan.target_rpm.map(|rpm| rpm.to_string()))),
+                ])
+            })
+            .collect();
+
+        let widths = [
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(8),
+            Constraint::Length(5),
+            Constraint::Length(6),
+            Constraint::Length(6),
+        ];
+
+        let panel = Block::default()
+            .title("Fan")
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .borders(Borders::ALL)
+            .border_style(
+                self.fans
+                    .iter()
+                    .filter_map(level)
+                    .max()
+                    .unwrap_or_default()
+                    .border_style(),
             );
+        let inner = panel.inner(block);
+        frame.render_widget(panel, block);
+
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(54), Constraint::Fill(1)])
+            .split(inner);
+
+        let fans = Table::new(rows, widths).header(
+            Row::new(vec!["Fan", "Profile", "Control", "Duty", "RPM", "Target"])
+                .style(Style::new().bold()),
+        );
+        frame.render_widget(fans, chunks[0]);
+
+        if self.fans.iter().any(|fan| !fan.curve.is_empty()) && chunks[1].width >= 20 {
+            self.render_curves(frame, chunks[1], config);
+        }
+    }
+
+    /// Duty by temperature of the current profile of each fan, with the current duty marked
+    fn render_curves(&self, frame: &mut Frame, area: Rect, config: &Config) {
+        // The curve and the current point of each fan
+        let points: Vec<[Vec<(f64, f64)>; 2]> = self
+            .fans
+            .iter()
+            .map(|fan| {
+                let curve = fan
+                    .curve
+                    .iter()
+                    .map(|point| (point.temperature as f64, duty(point.pwm) as f64))
+                    .collect();
+                let current = match (fan.temperature, fan.pwm) {
+                    (Some(temperature), Some(pwm)) if !fan.curve.is_empty() => {
+                        vec![(temperature as f64, duty(pwm) as f64)]
+                    }
+                    _ => Vec::new(),
+                };
+                [curve, current]
+            })
+            .collect();
+
+        let max = points
+            .iter()
+            .flat_map(|[curve, _]| curve.iter().map(|(temperature, _)| *temperature))
+            .fold(1.0, f64::max);
+        let unit = config.temperature_unit;
+        let label = |celsius: f64| {
+            Span::raw(format!(
+                "{:.0}{}",
+                unit.convert(celsius as f32),
+                unit.symbol()
+            ))
+        };
+
+        let datasets: Vec<Dataset> = points
+            .iter()
+            .enumerate()
+            .flat_map(|(index, [curve, current])| {
+                let color = COLORS[index % COLORS.len()];
+                [
+                    Dataset::default()
+                        .marker(symbols::Marker::Braille)
+                        .graph_type(GraphType::Line)
+                        .style(Style::new().fg(color))
+                        .data(curve),
+                    Dataset::default()
+                        .marker(symbols::Marker::Dot)
+                        .graph_type(GraphType::Scatter)
+                        .style(Style::new().fg(Color::Yellow).bold())
+                        .data(current),
+                ]
+            })
+            .collect();
+
+        let chart = Chart::new(datasets)
+            .x_axis(
+                Axis::default()
+                    .bounds([0.0, max])
+                    .labels([label(0.0), label(max / 2.0), label(max)])
+                    .style(Style::new().dim()),
+            )
+            .y_axis(
+                Axis::default()
+                    .bounds([0.0, 100.0])
+                    .labels([Span::raw("0%"), Span::raw("100%")])
+                    .style(Style::new().dim()),
+            );
+
+        frame.render_widget(chart, area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(temperature: f32, rpm: usize) -> CurvePoint {
+        CurvePoint {
+            temperature,
+            pwm: 0,
+            rpm: Some(rpm),
+        }
+    }
+
+    #[test]
+    fn interpolate_rpm() {
+        let curve = [
+            point(30.0, 1000),
+            point(50.0, 2000),
+            point(50.0, 3000),
+            point(70.0, 4000),
+        ];
+        let rpm =
+            |temperature| interpolate(&curve, temperature, |point| point.rpm.map(|rpm| rpm as f32));
 
-        frame.render_widget(fan, block);
+        assert_eq!(rpm(20.0), Some(1000.0));
+        assert_eq!(rpm(40.0), Some(1500.0));
+        assert_eq!(rpm(50.0), Some(2000.0));
+        assert_eq!(rpm(60.0), Some(3500.0));
+        assert_eq!(rpm(90.0), Some(4000.0));
     }
 }
@@ -1,17 +1,26 @@
 use anyhow::{Context, Result};
 use log::error;
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Style, Stylize},
     text::Line,
-    widgets::{Bar, BarChart, BarGroup, Block, Borders, Cell, Padding, Row, Table},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Cell, Padding, Paragraph, Row, Table},
 };
 use std::{
     fs::{self, File},
     io::{Read, Seek},
 };
 
+use crate::{collector::Collector, root};
+#[cfg(feature = "tui")]
+use crate::{
+    config::Config,
+    history::{SPARKLINE_WIDTH, Series},
+};
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Default)]
 pub struct GPU {
     pub load: Option<GPULoad>,
@@ -21,15 +30,28 @@ pub struct GPU {
 #[derive(Debug)]
 pub struct GPULoad {
     file: File,
-    load: f64,
+    pub load: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GpuSnapshot {
+    /// Percentage
+    pub load: Option<f64>,
+    /// MHz
+    pub frequency: Option<usize>,
+    /// MHz
+    pub max_frequency: Option<usize>,
 }
 
 impl GPULoad {
     fn new() -> Result<Option<Self>> {
         let gpu_names = ["gv11b", "gp10b", "ga10b", "gpu"];
 
-        let entries = fs::read_dir("/sys/class/devfreq")
-            .context("Failed to read from the directory /sys/class/devfreq")?;
+        let devfreq_path = root::path("/sys/class/devfreq");
+        let entries = fs::read_dir(&devfreq_path).context(format!(
+            "Failed to read from the directory {}",
+            devfreq_path.display()
+        ))?;
 
         for entry in entries.flatten() {
             let path = entry.path();
@@ -66,16 +88,19 @@ impl GPULoad {
 pub struct GPUFrequency {
     current_frequency_file: File,
     max_frequency_file: File,
-    current_frequency: usize,
-    max_frequency: usize,
+    pub current_frequency: usize,
+    pub max_frequency: usize,
 }
 
 impl GPUFrequency {
     fn new() -> Result<Option<Self>> {
         let gpu_names = ["gv11b", "gp10b", "ga10b", "gpu"];
 
-        let entries = fs::read_dir("/sys/class/devfreq")
-            .context("Failed to read from the directory /sys/class/devfreq")?;
+        let devfreq_path = root::path("/sys/class/devfreq");
+        let entries = fs::read_dir(&devfreq_path).context(format!(
+            "Failed to read from the directory {}",
+            devfreq_path.display()
+        ))?;
 
         for entry in entries.flatten() {
             let path = entry.path();
@@ -136,8 +161,10 @@ impl GPUFrequency {
     }
 }
 
-impl GPU {
-    pub fn new() -> Self {
+impl Collector for GPU {
+    type Snapshot = GpuSnapshot;
+
+    fn init() -> Result<Self> {
         let load = match GPULoad::new() {
             Ok(load) => load,
 
@@ -155,10 +182,10 @@ impl GPU {
             }
         };
 
-        Self { load, frequency }
+        Ok(Self { load, frequency })
     }
 
-    pub fn refresh(&mut self) {
+    fn refresh(&mut self) {
         if let Some(load) = &mut self.load
             && let Err(e) = load.refresh()
         {
@@ -172,9 +199,29 @@ impl GPU {
         }
     }
 
-    pub fn render(&self, frame: &mut Frame, block: Rect) {
+    fn snapshot(&self) -> GpuSnapshot {
+        GpuSnapshot {
+            load: self.load.as_ref().map(|load| load.load),
+            frequency: self
+                .frequency
+                .as_ref()
+                .map(|frequency| frequency.current_frequency),
+            max_frequency: self
+                .frequency
+                .as_ref()
+                .map(|frequency| frequency.max_frequency),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl GpuSnapshot {
+    pub fn render(&self, frame: &mut Frame, block: Rect, history: &Series, config: &Config) {
+        let level = config.thresholds.gpu.level(self.load.unwrap_or_default());
+
         let container = Block::default()
             .borders(Borders::ALL)
+            .border_style(level.border_style())
             .title("GPU")
             .padding(Padding::horizontal(1))
             .title_style(Style::new().bold());
@@ -190,15 +237,24 @@ impl GPU {
             (chunks[0], chunks[1])
         };
 
+        let (right_block, history_block) = {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(24), Constraint::Max(SPARKLINE_WIDTH)])
+                .spacing(1)
+                .split(right_block);
+
+            (chunks[0], chunks[1])
+        };
+
         let frequency = Table::new(
             [Row::new(vec![
                 Cell::new("Freq").style(Style::default().bold()),
-                Cell::new(match &self.frequency {
-                    Some(frequency) => format!(
-                        "{}MHz / {}MHz",
-                        frequency.current_frequency, frequency.max_frequency
-                    ),
-                    None => " - ".to_string(),
+                Cell::new(match (self.frequency, self.max_frequency) {
+                    (Some(frequency), Some(max_frequency)) => {
+                        format!("{}MHz / {}MHz", frequency, max_frequency)
+                    }
+                    _ => " - ".to_string(),
                 }),
             ])],
             [Constraint::Length(4), Constraint::Length(17)],
@@ -214,12 +270,13 @@ impl GPU {
             .data(
                 BarGroup::default().bars(&[Bar::default()
                     .label(Line::styled("Load", Style::default().bold()))
-                    .value(match &self.load {
-                        Some(load) => load.load.round() as u64,
+                    .value(match self.load {
+                        Some(load) => load.round() as u64,
                         None => 0,
                     })
-                    .text_value(match &self.load {
-                        Some(load) => format!("{:.1}% ", load.load),
+                    .style(level.style())
+                    .text_value(match self.load {
+                        Some(load) => format!("{:.1}% ", load),
                         None => " - ".to_string(),
                     })]),
             )
@@ -228,5 +285,9 @@ impl GPU {
         frame.render_widget(container, block);
         frame.render_widget(frequency, left_block);
         frame.render_widget(load, right_block);
+        frame.render_widget(
+            Paragraph::new(history.sparkline(history_block.width as usize, 0.0, 100.0)),
+            history_block,
+        );
     }
 }
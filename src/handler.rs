@@ -1,17 +1,530 @@
-use crate::app::{App, AppResult};
+use crate::{
+    app::{App, AppResult, Source, View},
+    collector::Collector,
+    popup::{Action, Popup},
+    process,
+};
 use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    if let Some(popup) = app.popup.take() {
+        app.popup = handle_popup_keys(key_event, popup, app);
+        return Ok(());
+    }
+
+    // The filter of the process view takes all the keys while it is typed
+    if app.view == View::Processes && app.process.editing {
+        handle_filter_keys(key_event, app);
+        return Ok(());
+    }
+
     match key_event.code {
-        KeyCode::Esc | KeyCode::Char('q') => {
+        KeyCode::Char('q') => {
+            app.quit();
+        }
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
             app.quit();
         }
-        KeyCode::Char('c') | KeyCode::Char('C') => {
-            if key_event.modifiers == KeyModifiers::CONTROL {
+        KeyCode::Esc => match app.view {
+            View::Main => app.quit(),
+            _ => app.view = View::Main,
+        },
+        _ => {
+            let handled = match app.view {
+                View::Main => handle_main_keys(key_event, app),
+                View::Charts => handle_chart_keys(key_event, app),
+                View::Processes => handle_process_keys(key_event, app),
+            };
+            if !handled {
+                handle_replay_keys(key_event, app);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_main_keys(key_event: KeyEvent, app: &mut App) -> bool {
+    match key_event.code {
+        KeyCode::Char('g') => app.view = View::Charts,
+        KeyCode::Char('t') => open_processes(app),
+        KeyCode::Char('m') => open_mode_popup(app),
+        KeyCode::Char('f') => open_fan_popup(app),
+        KeyCode::Char('i') => {
+            if let Some(mode) = &app.snapshot.power.mode {
+                app.popup = Some(Popup::ModeDetail {
+                    mode: mode.clone(),
+                    current: true,
+                });
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+fn read_only_popup() -> Popup {
+    Popup::Message {
+        text: "Read-only mode, the actions are disabled".to_string(),
+        error: true,
+    }
+}
+
+/// Opens the picker of the nvpmodel power modes, only live
+fn open_mode_popup(app: &mut App) {
+    if let Source::Replay(_) = app.source {
+        return;
+    }
+
+    let power = &app.snapshot.power;
+    app.popup = Some(if app.read_only {
+        read_only_popup()
+    } else if power.available_modes.is_empty() {
+        Popup::Message {
+            text: "No power mode found in /etc/nvpmodel.conf".to_string(),
+            error: true,
+        }
+    } else {
+        let current = power.mode.as_ref().map(|mode| mode.id);
+        Popup::Mode {
+            selected: power
+                .available_modes
+                .iter()
+                .position(|mode| Some(mode.id) == current)
+                .unwrap_or_default(),
+            modes: power.available_modes.clone(),
+            current,
+        }
+    });
+}
+
+/// Opens the picker of the fans, or directly the popup of the only one. Only live.
+fn open_fan_popup(app: &mut App) {
+    if let Source::Replay(_) = app.source {
+        return;
+    }
+
+    let fans = &app.snapshot.fan.fans;
+    app.popup = Some(if app.read_only {
+        read_only_popup()
+    } else if fans.is_empty() {
+        Popup::Message {
+            text: "No fan found".to_string(),
+            error: true,
+        }
+    } else if fans.len() == 1 {
+        fan_popup(app, 0)
+    } else {
+        Popup::Fans {
+            fans: fans.iter().map(|fan| fan.name.clone()).collect(),
+            selected: 0,
+        }
+    });
+}
+
+/// Confirms giving the fans back to nvfancontrol after a manual duty
+fn fan_auto_popup(app: &App) -> Popup {
+    Popup::Confirm {
+        message: "Start nvfancontrol to control the fans again?".to_string(),
+        action: Action::SetFanAuto {
+            systemctl: app.config.commands.systemctl.clone(),
+        },
+    }
+}
+
+/// The nvfancontrol profiles of a fan, or its manual duty when it has no profile
+fn fan_popup(app: &App, index: usize) -> Popup {
+    let Some(fan) = app.snapshot.fan.fans.get(index) else {
+        return Popup::Message {
+            text: "No fan found".to_string(),
+            error: true,
+        };
+    };
+
+    if fan.available_profiles.is_empty() {
+        return Popup::FanDuty {
+            fan: index,
+            value: String::new(),
+        };
+    }
+
+    Popup::FanProfile {
+        fan: index,
+        selected: fan
+            .available_profiles
+            .iter()
+            .position(|profile| Some(profile) == fan.profile.as_ref())
+            .unwrap_or_default(),
+        profiles: fan.available_profiles.clone(),
+        current: fan.profile.clone(),
+    }
+}
+
+/// The processes are not recorded, the view is only available live
+fn open_processes(app: &mut App) {
+    if let Source::Live(_) = app.source {
+        app.view = View::Processes;
+        app.processes.refresh();
+    }
+}
+
+fn handle_chart_keys(key_event: KeyEvent, app: &mut App) -> bool {
+    match key_event.code {
+        KeyCode::Char('g') => app.view = View::Main,
+        KeyCode::Char(c @ '1'..='4') => app.chart.set_window(c as usize - '1' as usize),
+        KeyCode::Down | KeyCode::Char('j') => app.chart.select_next(&app.history, &app.config),
+        KeyCode::Up | KeyCode::Char('k') => app.chart.select_previous(),
+        KeyCode::Char(' ') | KeyCode::Enter => app.chart.toggle(&app.history, &app.config),
+        KeyCode::Char('a') => app.chart.show_all(),
+        KeyCode::Char('t') => open_processes(app),
+        _ => return false,
+    }
+    true
+}
+
+fn handle_process_keys(key_event: KeyEvent, app: &mut App) -> bool {
+    let processes = &app.processes.processes;
+    match key_event.code {
+        KeyCode::Char('t') => app.view = View::Main,
+        KeyCode::Char('g') => app.view = View::Charts,
+        KeyCode::Down | KeyCode::Char('j') => app.process.scroll(processes, 1),
+        KeyCode::Up | KeyCode::Char('k') => app.process.scroll(processes, -1),
+        KeyCode::PageDown => app.process.page_down(processes),
+        KeyCode::PageUp => app.process.page_up(processes),
+        KeyCode::Home => app.process.first(processes),
+        KeyCode::End => app.process.last(processes),
+        KeyCode::Char('>') | KeyCode::Char('.') => app.process.sort_by_next(1),
+        KeyCode::Char('<') | KeyCode::Char(',') => app.process.sort_by_next(-1),
+        KeyCode::Char('r') => app.process.reverse(),
+        KeyCode::Char('/') => app.process.start_filter(),
+        KeyCode::Char('x') => open_action_popup(app, ProcessAction::Terminate),
+        KeyCode::Char('X') => open_action_popup(app, ProcessAction::Kill),
+        KeyCode::Char('n') => open_action_popup(app, ProcessAction::Renice),
+        KeyCode::Char('a') => open_action_popup(app, ProcessAction::Affinity),
+        _ => return false,
+    }
+    true
+}
+
+enum ProcessAction {
+    Terminate,
+    Kill,
+    Renice,
+    Affinity,
+}
+
+/// Opens the popup of an action on the selected process
+fn open_action_popup(app: &mut App, action: ProcessAction) {
+    if app.read_only {
+        app.popup = Some(read_only_popup());
+        return;
+    }
+
+    let Some(process) = app.process.selected(&app.processes.processes) else {
+        return;
+    };
+    let pid = process.pid;
+
+    let popup = match action {
+        ProcessAction::Terminate | ProcessAction::Kill => {
+            let (name, signal) = match action {
+                ProcessAction::Kill => ("SIGKILL", libc::SIGKILL),
+                _ => ("SIGTERM", libc::SIGTERM),
+            };
+            // The command lines can be long, the program is enough to recognize it
+            let program = process
+                .command
+                .split_whitespace()
+                .next()
+                .unwrap_or_default();
+            Popup::Confirm {
+                message: format!("Send {} to {} {}?", name, pid, program),
+                action: Action::Signal { pid, signal },
+            }
+        }
+        ProcessAction::Renice => Popup::Nice {
+            pid,
+            current: process.nice,
+            value: String::new(),
+        },
+        ProcessAction::Affinity => match process::affinity(pid) {
+            Ok(affinity) => Popup::Affinity {
+                pid,
+                // The online cores, cpu0 to cpuN
+                cores: app
+                    .snapshot
+                    .cpu
+                    .cores
+                    .iter()
+                    .filter_map(|core| core.name.strip_prefix("cpu")?.parse::<usize>().ok())
+                    .map(|core| (core, affinity.contains(&core)))
+                    .collect(),
+                selected: 0,
+            },
+            Err(e) => Popup::error(e),
+        },
+    };
+
+    app.popup = Some(popup);
+}
+
+/// Returns the popup to show next, if any
+fn handle_popup_keys(key_event: KeyEvent, popup: Popup, app: &mut App) -> Option<Popup> {
+    match popup {
+        Popup::Confirm { action, message } => match key_event.code {
+            KeyCode::Char('y') | KeyCode::Enter => run(action, app),
+            KeyCode::Char('n') | KeyCode::Esc => None,
+            _ => Some(Popup::Confirm { action, message }),
+        },
+        Popup::Nice {
+            pid,
+            current,
+            mut value,
+        } => match key_event.code {
+            KeyCode::Enter => match value.trim().parse::<i32>() {
+                Ok(nice) => run(Action::Renice { pid, nice }, app),
+                Err(_) => Some(Popup::Message {
+                    text: format!("Invalid nice value {}", value),
+                    error: true,
+                }),
+            },
+            KeyCode::Esc => None,
+            KeyCode::Backspace => {
+                value.pop();
+                Some(Popup::Nice {
+                    pid,
+                    current,
+                    value,
+                })
+            }
+            KeyCode::Char(c @ ('0'..='9' | '-')) => {
+                value.push(c);
+                Some(Popup::Nice {
+                    pid,
+                    current,
+                    value,
+                })
+            }
+            _ => Some(Popup::Nice {
+                pid,
+                current,
+                value,
+            }),
+        },
+        Popup::Affinity {
+            pid,
+            mut cores,
+            mut selected,
+        } => {
+            match key_event.code {
+                KeyCode::Enter => {
+                    let cores = cores
+                        .iter()
+                        .filter(|(_, enabled)| *enabled)
+                        .map(|(core, _)| *core)
+                        .collect();
+                    return run(Action::Affinity { pid, cores }, app);
+                }
+                KeyCode::Esc => return None,
+                KeyCode::Down | KeyCode::Char('j') => {
+                    selected = (selected + 1).min(cores.len().saturating_sub(1))
+                }
+                KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+                KeyCode::Char(' ') => {
+                    if let Some((_, enabled)) = cores.get_mut(selected) {
+                        *enabled = !*enabled;
+                    }
+                }
+                _ => {}
+            }
+            Some(Popup::Affinity {
+                pid,
+                cores,
+                selected,
+            })
+        }
+        Popup::Mode {
+            modes,
+            current,
+            mut selected,
+        } => {
+            match key_event.code {
+                KeyCode::Enter => {
+                    let mode = modes.get(selected)?.clone();
+                    return Some(Popup::Confirm {
+                        message: format!("Switch the power mode to {}?", mode.name),
+                        action: Action::SetMode {
+                            command: app.config.commands.nvpmodel.clone(),
+                            mode,
+                            reboot: false,
+                        },
+                    });
+                }
+                KeyCode::Char('i') => {
+                    let mode = modes.get(selected)?.clone();
+                    return Some(Popup::ModeDetail {
+                        current: Some(mode.id) == current,
+                        mode,
+                    });
+                }
+                KeyCode::Esc => return None,
+                KeyCode::Down | KeyCode::Char('j') => {
+                    selected = (selected + 1).min(modes.len().saturating_sub(1))
+                }
+                KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+                _ => {}
+            }
+            Some(Popup::Mode {
+                modes,
+                current,
+                selected,
+            })
+        }
+        Popup::Fans { fans, mut selected } => {
+            match key_event.code {
+                KeyCode::Enter => return Some(fan_popup(app, selected)),
+                KeyCode::Esc => return None,
+                KeyCode::Down | KeyCode::Char('j') => {
+                    selected = (selected + 1).min(fans.len().saturating_sub(1))
+                }
+                KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+                _ => {}
+            }
+            Some(Popup::Fans { fans, selected })
+        }
+        Popup::FanProfile {
+            fan,
+            profiles,
+            current,
+            mut selected,
+        } => {
+            match key_event.code {
+                KeyCode::Enter => {
+                    let profile = profiles.get(selected)?.clone();
+                    // The other fans keep the profile they run
+                    let mut fans: Vec<Option<String>> = app
+                        .snapshot
+                        .fan
+                        .fans
+                        .iter()
+                        .map(|fan| fan.profile.clone())
+                        .collect();
+                    *fans.get_mut(fan)? = Some(profile.clone());
+                    return Some(Popup::Confirm {
+                        message: format!(
+                            "Switch the profile of {} to {}? nvfancontrol restarts.",
+                            app.snapshot.fan.fans.get(fan)?.name,
+                            profile
+                        ),
+                        action: Action::SetFanProfile {
+                            systemctl: app.config.commands.systemctl.clone(),
+                            fan,
+                            profiles: fans,
+                        },
+                    });
+                }
+                KeyCode::Char('d') => {
+                    return Some(Popup::FanDuty {
+                        fan,
+                        value: String::new(),
+                    });
+                }
+                KeyCode::Char('a') => return Some(fan_auto_popup(app)),
+                KeyCode::Esc => return None,
+                KeyCode::Down | KeyCode::Char('j') => {
+                    selected = (selected + 1).min(profiles.len().saturating_sub(1))
+                }
+                KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+                _ => {}
+            }
+            Some(Popup::FanProfile {
+                fan,
+                profiles,
+                current,
+                selected,
+            })
+        }
+        Popup::FanDuty { fan, mut value } => match key_event.code {
+            KeyCode::Enter => match value.trim().parse::<u8>() {
+                Ok(duty) if duty <= 100 => {
+                    let fan = app.snapshot.fan.fans.get(fan)?.name.clone();
+                    Some(Popup::Confirm {
+                        message: format!(
+                            "Set the duty of {} to {}%? nvfancontrol is stopped first, a in the fan popup starts it again.",
+                            fan, duty
+                        ),
+                        action: Action::SetFanDuty {
+                            systemctl: app.config.commands.systemctl.clone(),
+                            fan,
+                            duty,
+                        },
+                    })
+                }
+                _ => Some(Popup::Message {
+                    text: format!("Invalid fan duty {}", value),
+                    error: true,
+                }),
+            },
+            KeyCode::Esc => None,
+            KeyCode::Char('a') => Some(fan_auto_popup(app)),
+            KeyCode::Backspace => {
+                value.pop();
+                Some(Popup::FanDuty { fan, value })
+            }
+            KeyCode::Char(c @ '0'..='9') => {
+                value.push(c);
+                Some(Popup::FanDuty { fan, value })
+            }
+            _ => Some(Popup::FanDuty { fan, value }),
+        },
+        // Only quitting leaves before the action returns
+        Popup::Running { text } => match key_event.code {
+            KeyCode::Char('q') => {
                 app.quit();
+                None
             }
+            KeyCode::Char('c') if key_event.modifiers == KeyModifiers::CONTROL => {
+                app.quit();
+                None
+            }
+            _ => Some(Popup::Running { text }),
+        },
+        Popup::ModeDetail { .. } | Popup::Message { .. } => None,
+    }
+}
+
+/// Runs an action, the failures are reported in a popup.
+/// The external commands run on a worker thread so the interface keeps refreshing.
+fn run(action: Action, app: &mut App) -> Option<Popup> {
+    match action.progress() {
+        Some(text) => {
+            app.spawn(action);
+            Some(Popup::Running { text })
         }
+        None => Popup::result(action.run()),
+    }
+}
+
+fn handle_filter_keys(key_event: KeyEvent, app: &mut App) {
+    match key_event.code {
+        KeyCode::Enter => app.process.apply_filter(),
+        KeyCode::Esc => app.process.clear_filter(),
+        KeyCode::Backspace => app.process.pop_filter(),
+        KeyCode::Char('c') if key_event.modifiers == KeyModifiers::CONTROL => app.quit(),
+        KeyCode::Char(c) => app.process.push_filter(c),
         _ => {}
     }
-    Ok(())
+}
+
+fn handle_replay_keys(key_event: KeyEvent, app: &mut App) {
+    if let Source::Replay(player) = &mut app.source {
+        match key_event.code {
+            KeyCode::Char(' ') | KeyCode::Char('p') => player.toggle_pause(),
+            KeyCode::Right | KeyCode::Char('l') => player.step_forward(),
+            KeyCode::Left | KeyCode::Char('h') => player.step_backward(),
+            KeyCode::Char('+') => player.speed_up(),
+            KeyCode::Char('-') => player.slow_down(),
+            _ => {}
+        }
+        app.snapshot = player.snapshot().clone();
+    }
 }
@@ -0,0 +1,154 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::snapshot::Snapshot;
+
+pub const DEFAULT_LENGTH: usize = 3600;
+
+/// Maximum width of the sparklines next to the bars
+pub const SPARKLINE_WIDTH: u16 = 30;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Ring buffer of the last values of a metric, with the time in seconds they were sampled at.
+#[derive(Debug, Clone, Default)]
+pub struct Series {
+    points: VecDeque<(f64, f64)>,
+}
+
+impl Series {
+    pub fn push(&mut self, time: f64, value: f64, length: usize) {
+        if self.points.len() >= length {
+            self.points.pop_front();
+        }
+        self.points.push_back((time, value));
+    }
+
+    pub fn points(&self) -> impl DoubleEndedIterator<Item = &(f64, f64)> {
+        self.points.iter()
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = f64> + '_ {
+        self.points.iter().map(|(_, value)| *value)
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        self.values().reduce(f64::min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        self.values().reduce(f64::max)
+    }
+
+    /// Draws the last `width` values, scaled between `min` and `max`.
+    pub fn sparkline(&self, width: usize, min: f64, max: f64) -> String {
+        let mut values: Vec<f64> = self.values().rev().take(width).collect();
+        values.reverse();
+
+        let range = max - min;
+
+        values
+            .into_iter()
+            .map(|value| {
+                let ratio = if range > 0.0 {
+                    ((value - min) / range).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                SPARKS[(ratio * (SPARKS.len() - 1) as f64).round() as usize]
+            })
+            .collect()
+    }
+}
+
+/// History of the metrics shown as graphs in the TUI.
+#[derive(Debug)]
+pub struct History {
+    length: usize,
+    /// Time of the last pushed sample
+    last_time: Option<f64>,
+    pub cpu: HashMap<String, Series>,
+    pub gpu: Series,
+    pub ram: Series,
+    pub swap: Series,
+    pub power: HashMap<String, Series>,
+    pub thermal: HashMap<String, Series>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_LENGTH)
+    }
+}
+
+impl History {
+    pub fn new(length: usize) -> Self {
+        Self {
+            length,
+            last_time: None,
+            cpu: HashMap::new(),
+            gpu: Series::default(),
+            ram: Series::default(),
+            swap: Series::default(),
+            power: HashMap::new(),
+            thermal: HashMap::new(),
+        }
+    }
+
+    /// Time of the last sample in seconds
+    pub fn last_time(&self) -> Option<f64> {
+        self.last_time
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.length);
+    }
+
+    pub fn push(&mut self, time: f64, snapshot: &Snapshot) {
+        match self.last_time {
+            // Same sample, e.g. a paused replay
+            Some(last_time) if last_time == time => return,
+            // Stepping back in a replay
+            Some(last_time) if last_time > time => self.clear(),
+            _ => {}
+        }
+        self.last_time = Some(time);
+
+        let length = self.length;
+
+        for core in &snapshot.cpu.cores {
+            self.cpu
+                .entry(core.name.clone())
+                .or_default()
+                .push(time, core.utilization, length);
+        }
+
+        if let Some(load) = snapshot.gpu.load {
+            self.gpu.push(time, load, length);
+        }
+
+        if let Some(mem) = &snapshot.memory.mem {
+            self.ram.push(time, mem.used_ram, length);
+            self.swap.push(time, mem.used_swap, length);
+        }
+
+        for channel in &snapshot.power.channels {
+            self.power.entry(channel.name.clone()).or_default().push(
+                time,
+                channel.power as f64,
+                length,
+            );
+        }
+
+        for sensor in &snapshot.thermal.sensors {
+            // Disabled sensors report bogus values
+            if sensor.temperature < -25.0 {
+                continue;
+            }
+            self.thermal.entry(sensor.name.clone()).or_default().push(
+                time,
+                sensor.temperature as f64,
+                length,
+            );
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Splits `http://<host>:<port>/<path>` into the host and the path, `default_path` when there is none.
+/// The host is only resolved when posting, so a DNS failure does not prevent starting.
+pub fn parse_url<'a>(url: &'a str, default_path: &'a str) -> Result<(&'a str, &'a str)> {
+    let url = url.strip_prefix("http://").ok_or(anyhow!(
+        "Invalid URL {}, expected http://<host>:<port>/<path>",
+        url
+    ))?;
+
+    let (host, path) = match url.find('/') {
+        Some(index) => (&url[..index], &url[index..]),
+        None => (url, default_path),
+    };
+
+    let valid = host
+        .rsplit_once(':')
+        .is_some_and(|(name, port)| !name.is_empty() && port.parse::<u16>().is_ok());
+    if !valid {
+        bail!(
+            "Invalid URL http://{}, expected http://<host>:<port>/<path>",
+            url
+        );
+    }
+
+    Ok((host, path))
+}
+
+/// Tries every address of `host` in turn, so an unreachable host does not block for the OS connect timeout.
+fn connect(host: &str) -> Result<TcpStream> {
+    let mut result = Err(anyhow!("Failed to resolve {}", host));
+    for addr in host
+        .to_socket_addrs()
+        .context(format!("Failed to resolve {}", host))?
+    {
+        result = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(anyhow::Error::from);
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    result.context(format!("Failed to connect to {}", host))
+}
+
+/// Minimal HTTP/1.1 POST, fails unless the response has a 2xx status.
+pub fn post(host: &str, path: &str, content_type: &str, body: &str) -> Result<()> {
+    let mut stream = connect(host)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+
+    let mut status_line = String::new();
+    BufReader::new(&stream).read_line(&mut status_line)?;
+
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => bail!(
+            "POST to http://{}{} failed: {}",
+            host,
+            path,
+            status_line.trim()
+        ),
+    }
+}
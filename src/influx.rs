@@ -0,0 +1,364 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    net::UdpSocket,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::error;
+
+use crate::{
+    collector::{Collector, Collectors},
+    engine::HWState,
+    http,
+    snapshot::Snapshot,
+    ticker::Ticker,
+};
+
+// Keep the datagrams under the usual MTU
+const UDP_PAYLOAD_SIZE: usize = 1400;
+
+#[derive(Debug)]
+pub enum Output {
+    Stdout,
+    Udp(UdpSocket),
+    Http { host: String, path: String },
+}
+
+impl Output {
+    /// Parses `-`, `udp://<host>:<port>` or `http://<host>:<port>/<path>?<query>`
+    pub fn new(target: &str) -> Result<Self> {
+        if target == "-" {
+            return Ok(Output::Stdout);
+        }
+
+        if let Some(addr) = target.strip_prefix("udp://") {
+            let socket = UdpSocket::bind("0.0.0.0:0")?;
+            socket
+                .connect(addr)
+                .context(format!("Failed to resolve {}", addr))?;
+            return Ok(Output::Udp(socket));
+        }
+
+        if target.starts_with("http://") {
+            let (host, path) = http::parse_url(target, "/write")?;
+            // The write endpoint rejects the points without ?db=<database>
+            if !path.contains('?') {
+                bail!(
+                    "Invalid influx target {}, expected the database in the query, e.g. http://{}/write?db=<database>",
+                    target,
+                    host
+                );
+            }
+
+            return Ok(Output::Http {
+                host: host.to_string(),
+                path: path.to_string(),
+            });
+        }
+
+        Err(anyhow!(
+            "Invalid influx target {}, expected -, udp://<host>:<port> or http://<host>:<port>/<path>?<query>",
+            target
+        ))
+    }
+
+    fn send(&self, lines: &str) -> Result<()> {
+        match self {
+            Output::Stdout => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(lines.as_bytes())?;
+                stdout.flush()?;
+            }
+            Output::Udp(socket) => {
+                let mut datagram = String::new();
+                for line in lines.lines() {
+                    if !datagram.is_empty() && datagram.len() + line.len() >= UDP_PAYLOAD_SIZE {
+                        socket.send(datagram.as_bytes())?;
+                        datagram.clear();
+                    }
+                    datagram.push_str(line);
+                    datagram.push('\n');
+                }
+                if !datagram.is_empty() {
+                    socket.send(datagram.as_bytes())?;
+                }
+            }
+            Output::Http { host, path } => {
+                http::post(host, path, "text/plain; charset=utf-8", lines)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// Renders all the metrics in the InfluxDB line protocol, one measurement per subsystem.
+pub fn lines(snapshot: &Snapshot, timestamp: u128) -> String {
+    let mut output = String::new();
+
+    for core in &snapshot.cpu.cores {
+        let _ = write!(
+            output,
+            "tegra_cpu,core={} utilization={}",
+            escape(&core.name),
+            core.utilization
+        );
+        if let Some(frequency) = core.frequency {
+            let _ = write!(output, ",frequency={}i", frequency);
+        }
+        let _ = writeln!(output, " {}", timestamp);
+    }
+
+    let mut gpu_fields: Vec<String> = Vec::new();
+    if let Some(load) = snapshot.gpu.load {
+        gpu_fields.push(format!("load={}", load));
+    }
+    if let Some(frequency) = snapshot.gpu.frequency {
+        gpu_fields.push(format!("frequency={}i", frequency));
+    }
+    if let Some(max_frequency) = snapshot.gpu.max_frequency {
+        gpu_fields.push(format!("max_frequency={}i", max_frequency));
+    }
+    if !gpu_fields.is_empty() {
+        let _ = writeln!(output, "tegra_gpu {} {}", gpu_fields.join(","), timestamp);
+    }
+
+    let mut memory_fields: Vec<String> = Vec::new();
+    if let Some(mem) = &snapshot.memory.mem {
+        memory_fields.push(format!("ram_used={}", mem.used_ram));
+        memory_fields.push(format!("ram_total={}", mem.total_ram));
+        memory_fields.push(format!("swap_used={}", mem.used_swap));
+        memory_fields.push(format!("swap_total={}", mem.total_swap));
+    }
+    if let Some(emc) = &snapshot.memory.emc {
+        memory_fields.push(format!("emc_frequency={}i", emc.frequency));
+        memory_fields.push(format!("emc_max_frequency={}i", emc.max_frequency));
+    }
+    if let Some(gpu_shared) = snapshot.memory.gpu_shared {
+        memory_fields.push(format!("gpu_shared={}", gpu_shared));
+    }
+    if !memory_fields.is_empty() {
+        let _ = writeln!(
+            output,
+            "tegra_memory {} {}",
+            memory_fields.join(","),
+            timestamp
+        );
+    }
+
+    for channel in &snapshot.power.channels {
+        let _ = writeln!(
+            output,
+            "tegra_power,rail={} power={} {}",
+            escape(&channel.name),
+            channel.power,
+            timestamp
+        );
+    }
+
+    for sensor in &snapshot.thermal.sensors {
+        // Sensors below -25 C are not connected
+        if sensor.temperature < -25.0 {
+            continue;
+        }
+        let _ = writeln!(
+            output,
+            "tegra_thermal,zone={} temperature={} {}",
+            escape(&sensor.name),
+            sensor.temperature,
+            timestamp
+        );
+    }
+
+    for fan in &snapshot.fan.fans {
+        let mut fan_fields = Vec::new();
+        if let Some(rpm) = fan.rpm {
+            fan_fields.push(format!("rpm={}i", rpm));
+        }
+        if let Some(pwm) = fan.pwm {
+            fan_fields.push(format!("pwm={}i", pwm));
+        }
+        if let Some(target_rpm) = fan.target_rpm {
+            fan_fields.push(format!("target_rpm={}i", target_rpm));
+        }
+        if !fan_fields.is_empty() {
+            let _ = writeln!(
+                output,
+                "tegra_fan,fan={} {} {}",
+                escape(&fan.name),
+                fan_fields.join(","),
+                timestamp
+            );
+        }
+    }
+
+    for hw in &snapshot.engine.hws {
+        let _ = writeln!(
+            output,
+            "tegra_engine,engine={} running={},frequency={}i {}",
+            escape(&hw.name.to_string()),
+            matches!(hw.state, HWState::Running),
+            // An integer in MHz like the other frequencies, a field keeps one type in InfluxDB
+            hw.frequency.round() as u64,
+            timestamp
+        );
+    }
+
+    output
+}
+
+/// Sends the metrics to `output` at every tick.
+/// Failed writes are logged and retried at the next sample.
+pub fn run(mut collectors: Collectors, output: Output, mut ticker: Ticker) -> Result<()> {
+    while ticker.wait() {
+        collectors.refresh();
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+
+        if let Err(e) = output.send(&lines(&collectors.snapshot(), timestamp)) {
+            if let Some(e) = e.downcast_ref::<io::Error>()
+                && e.kind() == io::ErrorKind::BrokenPipe
+            {
+                return Ok(());
+            }
+            error!("{}", e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read},
+        net::{TcpListener, UdpSocket},
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        engine::{HW, HWName},
+        thermal::SensorSnapshot,
+    };
+
+    fn snapshot(sensors: usize) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        snapshot.thermal.sensors = (0..sensors)
+            .map(|index| SensorSnapshot {
+                name: format!("zone {}", index),
+                temperature: 42.5,
+            })
+            .collect();
+        snapshot.engine.hws.push(HW {
+            name: HWName::NVENC,
+            state: HWState::Running,
+            frequency: 716.8,
+            processes: Vec::new(),
+        });
+        snapshot
+    }
+
+    #[test]
+    fn engine_line() {
+        assert_eq!(
+            lines(&snapshot(1), 7),
+            "tegra_thermal,zone=zone\\ 0 temperature=42.5 7\n\
+             tegra_engine,engine=NVENC running=true,frequency=717i 7\n"
+        );
+    }
+
+    #[test]
+    fn udp() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let output = Output::new(&format!("udp://{}", listener.local_addr().unwrap())).unwrap();
+
+        // More lines than fit in one datagram
+        let lines = lines(&snapshot(100), 7);
+        output.send(&lines).unwrap();
+
+        let mut received = String::new();
+        let mut buffer = [0; 2 * UDP_PAYLOAD_SIZE];
+        while received.len() < lines.len() {
+            let size = listener.recv(&mut buffer).unwrap();
+            assert!(size <= UDP_PAYLOAD_SIZE);
+            received.push_str(std::str::from_utf8(&buffer[..size]).unwrap());
+        }
+        assert_eq!(received, lines);
+    }
+
+    /// Answers one request with `status`, returns the request line and the body
+    fn serve(status: &'static str) -> (String, thread::JoinHandle<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut stream = &stream;
+            write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+
+            (
+                request_line.trim().to_string(),
+                String::from_utf8(body).unwrap(),
+            )
+        });
+
+        (format!("http://{}/write?db=jetson", addr), handle)
+    }
+
+    #[test]
+    fn http() {
+        let (url, handle) = serve("204 No Content");
+        let lines = lines(&snapshot(3), 7);
+        Output::new(&url).unwrap().send(&lines).unwrap();
+
+        let (request_line, body) = handle.join().unwrap();
+        assert_eq!(request_line, "POST /write?db=jetson HTTP/1.1");
+        assert_eq!(body, lines);
+    }
+
+    #[test]
+    fn http_error() {
+        let (url, handle) = serve("404 Not Found");
+        let error = Output::new(&url).unwrap().send("x").unwrap_err();
+        handle.join().unwrap();
+        assert!(error.to_string().contains("404 Not Found"));
+    }
+
+    #[test]
+    fn http_needs_database() {
+        assert!(Output::new("http://127.0.0.1:8086").is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    io::{self, Write},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    collector::{Collector, Collectors},
+    snapshot::Snapshot,
+    ticker::Ticker,
+};
+
+/// A line of the JSON stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    /// Seconds elapsed since the stream started
+    pub monotonic: f64,
+    /// Seconds since the Unix epoch
+    pub timestamp: f64,
+    #[serde(flatten)]
+    pub snapshot: Snapshot,
+}
+
+/// Samples every collector once and prints the result as a single JSON document.
+/// The utilization figures are computed over `interval` milliseconds.
+pub fn snapshot(collectors: &mut Collectors, interval: u64) -> Result<()> {
+    thread::sleep(Duration::from_millis(interval));
+    collectors.refresh();
+
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &collectors.snapshot())?;
+    writeln!(stdout)?;
+
+    Ok(())
+}
+
+/// Writes one JSON line at every tick until the writer is closed.
+pub fn stream(
+    collectors: &mut Collectors,
+    mut ticker: Ticker,
+    writer: &mut impl Write,
+) -> Result<()> {
+    let start = Instant::now();
+
+    while ticker.wait() {
+        collectors.refresh();
+
+        let sample = Sample {
+            monotonic: start.elapsed().as_secs_f64(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64(),
+            snapshot: collectors.snapshot(),
+        };
+
+        let written = serde_json::to_writer(&mut *writer, &sample)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(writer))
+            .and_then(|_| writer.flush());
+
+        match written {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
@@ -1,13 +1,33 @@
+#[cfg(feature = "tui")]
 pub mod app;
 
+#[cfg(feature = "tui")]
 pub mod event;
 
+#[cfg(feature = "tui")]
 pub mod ui;
 
+#[cfg(feature = "tui")]
 pub mod tui;
 
+#[cfg(feature = "tui")]
 pub mod handler;
 
+#[cfg(feature = "tui")]
+pub mod replay;
+
+#[cfg(feature = "tui")]
+pub mod chart;
+
+#[cfg(feature = "tui")]
+pub mod popup;
+
+#[cfg(feature = "tui")]
+pub mod config;
+
+#[cfg(feature = "tui")]
+pub mod alert;
+
 pub mod cpu;
 
 pub mod gpu;
@@ -16,6 +36,8 @@ pub mod memory;
 
 pub mod engine;
 
+pub mod device;
+
 pub mod disk;
 
 pub mod fan;
@@ -29,3 +51,30 @@ pub mod system;
 pub mod board;
 
 pub mod network;
+
+pub mod nvmap;
+
+pub mod process;
+
+pub mod root;
+
+pub mod collector;
+
+pub mod snapshot;
+
+pub mod history;
+
+pub mod ticker;
+
+#[cfg(feature = "tui")]
+pub mod cli;
+
+pub mod json;
+
+pub mod csv;
+
+pub mod prometheus;
+
+pub mod influx;
+
+pub mod http;
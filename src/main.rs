@@ -1,27 +1,162 @@
+use log::error;
 use ratatui::{Terminal, backend::CrosstermBackend};
-use std::io;
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    net::SocketAddr,
+    path::PathBuf,
+    str::FromStr,
+    thread,
+    time::Duration,
+};
 use tegratop::{
-    app::{App, AppResult},
+    alert::Alerts,
+    app::{App, AppResult, Source},
+    chart, cli,
+    collector::{Collector, Collectors},
+    config::Config,
+    csv::CsvLogger,
     event::{Event, EventHandler},
     handler::handle_key_events,
+    history::History,
+    influx, json, prometheus,
+    replay::Player,
+    root,
+    ticker::Ticker,
     tui::Tui,
+    ui::{self, Panel},
 };
 
 fn main() -> AppResult<()> {
-    env_logger::init();
+    let args = cli::cli().get_matches();
+
+    match args.get_one::<PathBuf>("log-file") {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            env_logger::Builder::from_default_env()
+                .target(env_logger::Target::Pipe(Box::new(file)))
+                .init();
+        }
+        None => env_logger::init(),
+    }
+
+    if let Some(path) = args.get_one::<String>("root") {
+        root::set(path);
+    }
 
-    let mut app = App::new();
+    let config = Config::load(args.get_one::<PathBuf>("config").map(PathBuf::as_path))?;
+
+    // The command line takes precedence over the configuration
+    let interval = args
+        .get_one::<u64>("interval")
+        .copied()
+        .or(config.interval)
+        .unwrap_or(1_000);
+    let once = args.get_flag("once");
+
+    let mut app = match args.subcommand() {
+        Some(("json", _)) => {
+            json::snapshot(&mut Collectors::new(), interval)?;
+            return Ok(());
+        }
+        Some(("stream", _)) => {
+            let ticker = Ticker::new(interval, once);
+            json::stream(&mut Collectors::new(), ticker, &mut io::stdout().lock())?;
+            return Ok(());
+        }
+        Some(("exporter", args)) => {
+            let addr = args.get_one::<SocketAddr>("address").unwrap();
+            prometheus::serve(Collectors::new(), *addr, interval)?;
+            return Ok(());
+        }
+        Some(("textfile", args)) => {
+            let dir = args.get_one::<PathBuf>("dir").unwrap();
+            prometheus::textfile(Collectors::new(), dir, Ticker::new(interval, once))?;
+            return Ok(());
+        }
+        Some(("influx", args)) => {
+            let output = influx::Output::new(args.get_one::<String>("target").unwrap())?;
+            influx::run(Collectors::new(), output, Ticker::new(interval, once))?;
+            return Ok(());
+        }
+        Some(("record", args)) => {
+            let path = args.get_one::<PathBuf>("file").unwrap();
+            let file = File::create(path)?;
+            let ticker = Ticker::new(interval, once);
+            json::stream(&mut Collectors::new(), ticker, &mut BufWriter::new(file))?;
+            return Ok(());
+        }
+        Some(("replay", args)) => {
+            App::replay(Player::load(args.get_one::<PathBuf>("file").unwrap())?)
+        }
+        _ => App::new(),
+    };
+
+    // By default keep enough samples for the longest chart window, or the whole recording
+    let length = match (args.get_one::<u64>("history"), &app.source) {
+        (Some(length), _) => *length as usize,
+        (None, Source::Live(_)) => chart::history_length(interval),
+        (None, Source::Replay(player)) => player.sample_count(),
+    };
+    app.history = History::new(length);
+    if let Some(panels) = args.get_many::<String>("panels") {
+        app.panels = panels
+            .map(|panel| Panel::from_str(panel).unwrap())
+            .collect();
+    } else if let Some(panels) = &config.panels {
+        app.panels = panels.clone();
+    }
+    app.alerts = Alerts::new(&config.alerts)?;
+    app.config = config;
+    app.read_only = args.get_flag("read-only");
+    app.no_color =
+        args.get_flag("no-color") || env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+
+    if once {
+        if let Source::Live(_) = app.source {
+            // The utilization figures need two samples
+            thread::sleep(Duration::from_millis(interval));
+            app.tick();
+        }
+        let (width, _) = ratatui::crossterm::terminal::size().unwrap_or((120, 0));
+        io::stdout().write_all(ui::print(&mut app, width)?.as_bytes())?;
+        return Ok(());
+    }
+
+    let tick_rate = match app.source {
+        Source::Live(_) => interval,
+        // Tick faster than the recording to follow the playback speed
+        Source::Replay(_) => 100,
+    };
+
+    let mut csv_logger = match app.source {
+        Source::Live(_) => args
+            .get_one::<String>("log-csv")
+            .map(CsvLogger::new)
+            .transpose()?,
+        Source::Replay(_) => None,
+    };
 
     let backend = CrosstermBackend::new(io::stdout());
     let terminal = Terminal::new(backend)?;
-    let events = EventHandler::new(1_000);
+    let events = EventHandler::new(tick_rate);
     let mut tui = Tui::new(terminal, events);
     tui.init()?;
 
     while app.running {
+        app.poll_action();
         tui.draw(&mut app)?;
         match tui.events.next()? {
-            Event::Tick => app.tick(),
+            Event::Tick => {
+                app.tick();
+
+                if let Some(logger) = &mut csv_logger
+                    && let Err(e) = logger.write(&app.snapshot)
+                {
+                    error!("{}", e);
+                }
+            }
             Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
             _ => {}
         }
@@ -1,22 +1,34 @@
 use anyhow::{Context, Result};
 use log::error;
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Style, Stylize},
     text::Line,
-    widgets::{Bar, BarChart, BarGroup, Block, Borders, Cell, Padding, Row, Table},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Cell, Padding, Paragraph, Row, Table},
 };
 
 use std::{
     fs::{self, File},
     io::{Read, Seek},
+    path::PathBuf,
 };
 
+use crate::{collector::Collector, nvmap::Nvmap, root};
+#[cfg(feature = "tui")]
+use crate::{
+    config::Config,
+    history::{SPARKLINE_WIDTH, Series},
+};
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Default)]
 pub struct Memory {
     pub mem: Option<Mem>,
     pub emc: Option<EMC>,
+    /// Total nvmap allocations in MB
+    pub gpu_shared: Option<f64>,
 }
 
 #[derive(Debug)]
@@ -30,26 +42,56 @@ pub struct Mem {
 
 #[derive(Debug)]
 pub struct EMC {
+    current_frequency_path: PathBuf,
     max_frequency_fd: File,
-    current_frequency: usize,
-    max_frequency: usize,
+    pub current_frequency: usize,
+    pub max_frequency: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemorySnapshot {
+    pub mem: Option<MemSnapshot>,
+    pub emc: Option<EmcSnapshot>,
+    /// MB of RAM mapped for the GPU and the other engines
+    pub gpu_shared: Option<f64>,
+}
+
+/// MB
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemSnapshot {
+    pub total_ram: f64,
+    pub used_ram: f64,
+    pub total_swap: f64,
+    pub used_swap: f64,
+}
+
+/// MHz
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmcSnapshot {
+    pub frequency: usize,
+    pub max_frequency: usize,
 }
 
 impl EMC {
     fn new() -> Result<Self> {
-        let current_frequency = fs::read_to_string("/sys/kernel/debug/clk/emc/clk_rate")
-            .context("Failed to read from /sys/kernel/debug/clk/emc/clk_rate")?;
+        let current_frequency_path = root::path("/sys/kernel/debug/clk/emc/clk_rate");
+        let current_frequency = fs::read_to_string(&current_frequency_path).context(format!(
+            "Failed to read from {}",
+            current_frequency_path.display()
+        ))?;
 
         let current_frequency = current_frequency.trim().parse::<usize>()? / 1_000_000;
 
-        let mut max_frequency_fd = File::open("/sys/kernel/debug/clk/emc/clk_max_rate")
-            .context("Failed to open /sys/kernel/debug/clk/emc/clk_max_rate")?;
+        let max_frequency_path = root::path("/sys/kernel/debug/clk/emc/clk_max_rate");
+        let mut max_frequency_fd = File::open(&max_frequency_path)
+            .context(format!("Failed to open {}", max_frequency_path.display()))?;
 
         let mut buffer = String::new();
         max_frequency_fd.read_to_string(&mut buffer)?;
         let max_frequency = buffer.trim().parse::<usize>()? / 1_000_000;
 
         Ok(Self {
+            current_frequency_path,
             max_frequency_fd,
             current_frequency,
             max_frequency,
@@ -62,8 +104,11 @@ impl EMC {
         self.max_frequency_fd.read_to_string(&mut buffer)?;
         self.max_frequency = buffer.trim().parse::<usize>()? / 1_000_000;
 
-        let current_frequency = fs::read_to_string("/sys/kernel/debug/clk/emc/clk_rate")
-            .context("Failed to read from /sys/kernel/debug/clk/emc/clk_rate")?;
+        let current_frequency =
+            fs::read_to_string(&self.current_frequency_path).context(format!(
+                "Failed to read from {}",
+                self.current_frequency_path.display()
+            ))?;
         self.current_frequency = current_frequency.trim().parse::<usize>()? / 1_000_000;
 
         Ok(())
@@ -72,7 +117,9 @@ impl EMC {
 
 impl Mem {
     pub fn new() -> Result<Self> {
-        let mut memory_file = File::open("/proc/meminfo").context("Faile to open /proc/meminfo")?;
+        let meminfo_path = root::path("/proc/meminfo");
+        let mut memory_file = File::open(&meminfo_path)
+            .context(format!("Failed to open {}", meminfo_path.display()))?;
         let mut buffer = String::new();
         memory_file.read_to_string(&mut buffer)?;
 
@@ -198,8 +245,10 @@ impl Mem {
     }
 }
 
-impl Memory {
-    pub fn new() -> Self {
+impl Collector for Memory {
+    type Snapshot = MemorySnapshot;
+
+    fn init() -> Result<Self> {
         let emc = EMC::new().map_or_else(
             |e| {
                 error!("{}", e);
@@ -216,10 +265,22 @@ impl Memory {
             Some,
         );
 
-        Self { mem, emc }
+        let gpu_shared = Nvmap::read().map_or_else(
+            |e| {
+                error!("{}", e);
+                None
+            },
+            |nvmap| Some((nvmap.total as f64 / 1024.0).round()),
+        );
+
+        Ok(Self {
+            mem,
+            emc,
+            gpu_shared,
+        })
     }
 
-    pub fn refresh(&mut self) {
+    fn refresh(&mut self) {
         if let Some(mem) = &mut self.mem
             && let Err(e) = mem.refresh()
         {
@@ -231,11 +292,60 @@ impl Memory {
         {
             error!("{}", e);
         }
+
+        if self.gpu_shared.is_some() {
+            match Nvmap::read() {
+                Ok(nvmap) => self.gpu_shared = Some((nvmap.total as f64 / 1024.0).round()),
+                Err(e) => error!("{}", e),
+            }
+        }
     }
 
-    pub fn render(&self, frame: &mut Frame, block: Rect) {
+    fn snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot {
+            mem: self.mem.as_ref().map(|mem| MemSnapshot {
+                total_ram: mem.total_ram,
+                used_ram: mem.used_ram,
+                total_swap: mem.total_swap,
+                used_swap: mem.used_swap,
+            }),
+            emc: self.emc.as_ref().map(|emc| EmcSnapshot {
+                frequency: emc.current_frequency,
+                max_frequency: emc.max_frequency,
+            }),
+            gpu_shared: self.gpu_shared,
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl MemorySnapshot {
+    pub fn render(
+        &self,
+        frame: &mut Frame,
+        block: Rect,
+        ram_history: &Series,
+        swap_history: &Series,
+        config: &Config,
+    ) {
+        // Percentages of the totals
+        let (ram_level, swap_level) = match &self.mem {
+            Some(mem) => (
+                config
+                    .thresholds
+                    .ram
+                    .level(percent(mem.used_ram, mem.total_ram)),
+                config
+                    .thresholds
+                    .swap
+                    .level(percent(mem.used_swap, mem.total_swap)),
+            ),
+            None => Default::default(),
+        };
+
         let container = Block::default()
             .borders(Borders::ALL)
+            .border_style(ram_level.max(swap_level).border_style())
             .title("Memory")
             .padding(Padding::horizontal(1))
             .title_style(Style::new().bold());
@@ -255,6 +365,31 @@ impl Memory {
             (chunks[0], chunks[1], chunks[2])
         };
 
+        let (ram_block, ram_history_block) = {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(24), Constraint::Max(SPARKLINE_WIDTH)])
+                .spacing(1)
+                .split(ram_block);
+
+            (chunks[0], chunks[1])
+        };
+
+        let (swap_block, swap_history_block) = {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(24), Constraint::Max(SPARKLINE_WIDTH)])
+                .spacing(1)
+                .split(swap_block);
+
+            (chunks[0], chunks[1])
+        };
+
+        let (total_ram, total_swap) = match &self.mem {
+            Some(mem) => (mem.total_ram, mem.total_swap),
+            None => (0.0, 0.0),
+        };
+
         let ram = BarChart::default()
             .block(Block::default())
             .bar_width(1)
@@ -266,6 +401,7 @@ impl Memory {
                     Some(mem) => Bar::default()
                         .label(Line::styled("RAM ", Style::default().bold()))
                         .value(mem.used_ram as u64)
+                        .style(ram_level.style())
                         .text_value(format!("{}MB / {}MB ", mem.used_ram, mem.total_ram)),
 
                     None => Bar::default()
@@ -289,6 +425,7 @@ impl Memory {
                     Some(mem) => Bar::default()
                         .label(Line::styled("Swap", Style::default().bold()))
                         .value(mem.used_swap as u64)
+                        .style(swap_level.style())
                         .text_value(format!("{}MB / {}MB ", mem.used_swap, mem.total_swap)),
 
                     None => Bar::default()
@@ -305,17 +442,48 @@ impl Memory {
             [Row::new(vec![
                 Cell::new("EMC").style(Style::default().bold()),
                 Cell::new(match &self.emc {
-                    Some(emc) => format!("{}MHz / {}MHz", emc.current_frequency, emc.max_frequency),
+                    Some(emc) => format!("{}MHz / {}MHz", emc.frequency, emc.max_frequency),
+                    None => " - ".to_string(),
+                }),
+                Cell::new("GPU shared").style(Style::default().bold()),
+                Cell::new(match self.gpu_shared {
+                    Some(gpu_shared) => format!("{}MB", gpu_shared),
                     None => " - ".to_string(),
                 }),
             ])],
-            [Constraint::Length(4), Constraint::Length(20)],
+            [
+                Constraint::Length(4),
+                Constraint::Length(20),
+                Constraint::Length(10),
+                Constraint::Length(10),
+            ],
         )
         .block(Block::default());
 
         frame.render_widget(container, block);
         frame.render_widget(ram, ram_block);
         frame.render_widget(swap, swap_block);
+        frame.render_widget(
+            Paragraph::new(ram_history.sparkline(ram_history_block.width as usize, 0.0, total_ram)),
+            ram_history_block,
+        );
+        frame.render_widget(
+            Paragraph::new(swap_history.sparkline(
+                swap_history_block.width as usize,
+                0.0,
+                total_swap,
+            )),
+            swap_history_block,
+        );
         frame.render_widget(emc, emc_block);
     }
 }
+
+#[cfg(feature = "tui")]
+fn percent(used: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        used / total * 100.0
+    } else {
+        0.0
+    }
+}
@@ -1,30 +1,42 @@
+use anyhow::Result;
 use std::ffi::CStr;
 use std::net::Ipv4Addr;
 
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
     layout::{Constraint, Rect},
     style::{Style, Stylize},
     widgets::{Block, Borders, Padding, Row, Table},
 };
+use serde::{Deserialize, Serialize};
+
+use crate::collector::Collector;
 
 #[derive(Debug, Default)]
 pub struct Network {
     pub interfaces: Vec<Interface>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkSnapshot {
+    pub interfaces: Vec<Interface>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Interface {
-    name: String,
-    ipv4: Option<Ipv4Addr>,
+    pub name: String,
+    pub ipv4: Option<Ipv4Addr>,
 }
 
-impl Network {
-    pub fn new() -> Self {
-        Self::default()
+impl Collector for Network {
+    type Snapshot = NetworkSnapshot;
+
+    fn init() -> Result<Self> {
+        Ok(Self::default())
     }
 
-    pub fn refresh(&mut self) {
+    fn refresh(&mut self) {
         let mut interfaces: Vec<Interface> = vec![];
         unsafe {
             let mut ifap: *mut libc::ifaddrs = std::ptr::null_mut();
@@ -72,6 +84,15 @@ impl Network {
         self.interfaces = interfaces;
     }
 
+    fn snapshot(&self) -> NetworkSnapshot {
+        NetworkSnapshot {
+            interfaces: self.interfaces.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl NetworkSnapshot {
     pub fn render(&self, frame: &mut Frame, block: Rect) {
         let rows: Vec<Row> = self
             .interfaces
@@ -0,0 +1,64 @@
+use std::{collections::HashMap, fs};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::root;
+
+/// Memory mapped for the GPU and the other engines, by client. Needs root to read debugfs.
+const CLIENTS: &str = "/sys/kernel/debug/nvmap/iovmm/clients";
+
+/// Accounting of the nvmap allocations, the memory the GPU shares with the CPU.
+#[derive(Debug, Default)]
+pub struct Nvmap {
+    /// KiB by pid
+    pub clients: HashMap<u32, u64>,
+    /// KiB
+    pub total: u64,
+}
+
+impl Nvmap {
+    /// Reads lines like `user  python3  1234  45056K`, followed by a `total  1234K` line
+    pub fn read() -> Result<Self> {
+        let path = root::path(CLIENTS);
+        let content =
+            fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+
+        let mut clients: HashMap<u32, u64> = HashMap::new();
+        let mut total = None;
+
+        for line in content.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            match fields.as_slice() {
+                ["total", size] => total = Some(parse_size(size)?),
+                [.., pid, size] => {
+                    // Kernel clients have no pid
+                    if let Ok(pid) = pid.parse::<u32>() {
+                        *clients.entry(pid).or_default() += parse_size(size)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let total = total.unwrap_or(clients.values().sum());
+
+        Ok(Self { clients, total })
+    }
+}
+
+/// Parses `45056K`, `44M` or a number of bytes to KiB
+fn parse_size(size: &str) -> Result<u64> {
+    let (number, unit) = size.split_at(size.trim_end_matches(char::is_alphabetic).len());
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| anyhow!("Invalid nvmap size {}", size))?;
+
+    match unit {
+        "" | "B" => Ok(number / 1024),
+        "K" => Ok(number),
+        "M" => Ok(number * 1024),
+        "G" => Ok(number * 1024 * 1024),
+        _ => Err(anyhow!("Invalid nvmap size {}", size)),
+    }
+}
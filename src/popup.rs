@@ -0,0 +1,449 @@
+use anyhow::Result;
+use log::error;
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{Block, Borders, Clear, Padding, Paragraph, Wrap},
+};
+
+use crate::{
+    fan,
+    power::{self, Mode, ModeChange},
+    process,
+};
+
+/// An action that changes the system, disabled by `--read-only`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Signal {
+        pid: u32,
+        signal: i32,
+    },
+    Renice {
+        pid: u32,
+        nice: i32,
+    },
+    Affinity {
+        pid: u32,
+        cores: Vec<usize>,
+    },
+    /// Runs `<command> -m <id>`
+    SetMode {
+        command: String,
+        mode: Mode,
+        reboot: bool,
+    },
+    /// Runs `<systemctl> stop nvfancontrol`, rewrites the configuration and starts it again.
+    /// `profiles` has the profile of every fan, the one of `fan` is the new one.
+    SetFanProfile {
+        systemctl: String,
+        fan: usize,
+        profiles: Vec<Option<String>>,
+    },
+    /// Percent, nvfancontrol is stopped with `<systemctl> stop nvfancontrol` when it runs
+    SetFanDuty {
+        systemctl: String,
+        fan: String,
+        duty: u8,
+    },
+    /// Runs `<systemctl> start nvfancontrol`
+    SetFanAuto {
+        systemctl: String,
+    },
+}
+
+impl Action {
+    /// What the action is doing while it runs on the worker thread.
+    /// `None` for the actions quick enough to run in the event loop.
+    pub fn progress(&self) -> Option<String> {
+        match self {
+            Action::SetMode { mode, .. } => Some(format!("Setting the power mode {}", mode.name)),
+            // systemctl waits for nvfancontrol to stop and start
+            Action::SetFanProfile { .. } => Some("Restarting nvfancontrol".to_string()),
+            Action::SetFanDuty { fan, .. } => Some(format!("Setting the duty of {}", fan)),
+            Action::SetFanAuto { .. } => Some("Starting nvfancontrol".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Returns the popup to show next, if any
+    pub fn run(&self) -> Result<Option<Popup>> {
+        match self {
+            Action::Signal { pid, signal } => process::kill(*pid, *signal)?,
+            Action::Renice { pid, nice } => process::renice(*pid, *nice)?,
+            Action::Affinity { pid, cores } => process::set_affinity(*pid, cores)?,
+            Action::SetMode {
+                command,
+                mode,
+                reboot,
+            } => {
+                let popup = match power::set_mode(command, mode.id, *reboot)? {
+                    ModeChange::Done if *reboot => Popup::Message {
+                        text: format!("Rebooting to apply the power mode {}", mode.name),
+                        error: false,
+                    },
+                    ModeChange::Done => Popup::Message {
+                        text: format!("Power mode set to {}", mode.name),
+                        error: false,
+                    },
+                    ModeChange::RebootRequired => Popup::Confirm {
+                        message: format!(
+                            "The power mode {} needs a reboot. Reboot now?",
+                            mode.name
+                        ),
+                        action: Action::SetMode {
+                            command: command.clone(),
+                            mode: mode.clone(),
+                            reboot: true,
+                        },
+                    },
+                };
+                return Ok(Some(popup));
+            }
+            Action::SetFanProfile {
+                systemctl,
+                fan,
+                profiles,
+            } => {
+                fan::set_profiles(systemctl, profiles)?;
+                return Ok(Some(Popup::Message {
+                    text: format!(
+                        "Fan profile set to {}",
+                        profiles.get(*fan).cloned().flatten().unwrap_or_default()
+                    ),
+                    error: false,
+                }));
+            }
+            Action::SetFanDuty {
+                systemctl,
+                fan,
+                duty,
+            } => {
+                fan::set_pwm(systemctl, fan, (*duty as u32 * 255).div_ceil(100) as u8)?;
+                return Ok(Some(Popup::Message {
+                    text: format!("Duty of {} set to {}%", fan, duty),
+                    error: false,
+                }));
+            }
+            Action::SetFanAuto { systemctl } => {
+                fan::set_auto(systemctl)?;
+                return Ok(Some(Popup::Message {
+                    text: "nvfancontrol controls the fans again".to_string(),
+                    error: false,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Dialog drawn over the current view, it takes all the keys while it is open.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Popup {
+    /// `y` or `Enter` runs the action, `n` or `Esc` cancels
+    Confirm { message: String, action: Action },
+    /// Nice value being typed for a process
+    Nice {
+        pid: u32,
+        current: i32,
+        value: String,
+    },
+    /// Cores of a process toggled with `Space`, `Enter` applies
+    Affinity {
+        pid: u32,
+        cores: Vec<(usize, bool)>,
+        selected: usize,
+    },
+    /// Power modes of nvpmodel.conf, `Enter` switches to the selected one
+    Mode {
+        modes: Vec<Mode>,
+        current: Option<u8>,
+        selected: usize,
+    },
+    /// Fans to control, `Enter` opens the profiles of the selected one
+    Fans { fans: Vec<String>, selected: usize },
+    /// Profiles of a fan in nvfancontrol.conf, `Enter` switches to the selected one
+    FanProfile {
+        fan: usize,
+        profiles: Vec<String>,
+        current: Option<String>,
+        selected: usize,
+    },
+    /// Duty of a fan being typed, in percent
+    FanDuty { fan: usize, value: String },
+    /// What a power mode constrains, any key closes it
+    ModeDetail { mode: Mode, current: bool },
+    /// Action running on the worker thread, replaced by its result
+    Running { text: String },
+    /// Result of an action, any key closes it
+    Message { text: String, error: bool },
+}
+
+impl Popup {
+    pub fn error(e: anyhow::Error) -> Self {
+        Popup::Message {
+            // The cause, e.g. the EPERM of a signal, is the useful part
+            text: format!("{:#}", e),
+            error: true,
+        }
+    }
+
+    /// The popup to show after an action, the failures are logged and reported
+    pub fn result(result: Result<Option<Popup>>) -> Option<Popup> {
+        match result {
+            Ok(popup) => popup,
+            Err(e) => {
+                error!("{:#}", e);
+                Some(Popup::error(e))
+            }
+        }
+    }
+
+    fn title(&self) -> &str {
+        match self {
+            Popup::Confirm { .. } => "Confirm",
+            Popup::Nice { .. } => "Nice",
+            Popup::Affinity { .. } => "CPU affinity",
+            Popup::Mode { .. } => "Power mode",
+            Popup::ModeDetail { .. } => "Power mode detail",
+            Popup::Fans { .. } => "Fan",
+            Popup::FanProfile { .. } => "Fan profile",
+            Popup::FanDuty { .. } => "Fan duty",
+            Popup::Running { .. } => "Running",
+            Popup::Message { error: true, .. } => "Error",
+            Popup::Message { error: false, .. } => "Done",
+        }
+    }
+
+    fn lines(&self) -> Vec<Line<'_>> {
+        match self {
+            Popup::Confirm { message, .. } => vec![
+                Line::from(message.as_str()),
+                Line::default(),
+                Line::from("y: yes  n: no").dim(),
+            ],
+            Popup::Nice {
+                pid,
+                current,
+                value,
+            } => vec![
+                Line::from(format!("Nice value of {}, currently {}", pid, current)),
+                Line::from(format!("New value, -20 to 19: {}_", value)),
+                Line::default(),
+                Line::from("Enter: apply  Esc: cancel").dim(),
+            ],
+            Popup::Affinity {
+                pid,
+                cores,
+                selected,
+            } => {
+                let mut lines = vec![Line::from(format!("Cores of {}", pid)), Line::default()];
+                lines.extend(cores.iter().enumerate().map(|(index, (core, enabled))| {
+                    let line = Line::from(format!(
+                        "[{}] CPU {}",
+                        if *enabled { "x" } else { " " },
+                        core
+                    ));
+                    if index == *selected {
+                        line.reversed()
+                    } else {
+                        line
+                    }
+                }));
+                lines.push(Line::default());
+                lines.push(Line::from("Space: toggle  Enter: apply  Esc: cancel").dim());
+                lines
+            }
+            Popup::Mode {
+                modes,
+                current,
+                selected,
+            } => {
+                let mut lines: Vec<Line> = modes
+                    .iter()
+                    .enumerate()
+                    .map(|(index, mode)| {
+                        let marker = if Some(mode.id) == *current { "*" } else { " " };
+                        let line = Line::from(format!("{} {:>2} {}", marker, mode.id, mode.name));
+                        if index == *selected {
+                            line.reversed()
+                        } else {
+                            line
+                        }
+                    })
+                    .collect();
+                lines.push(Line::default());
+                lines.push(Line::from("Enter: switch  i: detail  Esc: cancel").dim());
+                lines
+            }
+            Popup::Fans { fans, selected } => {
+                let mut lines: Vec<Line> = fans
+                    .iter()
+                    .enumerate()
+                    .map(|(index, fan)| {
+                        let line = Line::from(fan.as_str());
+                        if index == *selected {
+                            line.reversed()
+                        } else {
+                            line
+                        }
+                    })
+                    .collect();
+                lines.push(Line::default());
+                lines.push(Line::from("Enter: select  Esc: cancel").dim());
+                lines
+            }
+            Popup::FanProfile {
+                profiles,
+                current,
+                selected,
+                ..
+            } => {
+                let mut lines: Vec<Line> = profiles
+                    .iter()
+                    .enumerate()
+                    .map(|(index, profile)| {
+                        let marker = if Some(profile) == current.as_ref() {
+                            "*"
+                        } else {
+                            " "
+                        };
+                        let line = Line::from(format!("{} {}", marker, profile));
+                        if index == *selected {
+                            line.reversed()
+                        } else {
+                            line
+                        }
+                    })
+                    .collect();
+                lines.push(Line::default());
+                lines.push(Line::from("Enter: switch  d: manual duty  a: auto  Esc: cancel").dim());
+                lines
+            }
+            Popup::FanDuty { value, .. } => vec![
+                Line::from(format!("Fan duty, 0 to 100%: {}_", value)),
+                Line::default(),
+                Line::from("Enter: apply  a: auto  Esc: cancel").dim(),
+            ],
+            Popup::ModeDetail { mode, current } => {
+                let mut rows: Vec<(String, String)> = vec![(
+                    "Power budget".to_string(),
+                    match mode.power_budget {
+                        Some(watts) => format!("{} W", watts),
+                        None => "no limit".to_string(),
+                    },
+                )];
+
+                if !mode.cpu_online.is_empty() {
+                    let cores = |online: bool| {
+                        mode.cpu_online
+                            .iter()
+                            .filter(|(_, state)| *state == online)
+                            .map(|(core, _)| core.to_string())
+                            .collect::<Vec<String>>()
+                            .join(",")
+                    };
+                    let mut text = format!("online {}", cores(true));
+                    let offline = cores(false);
+                    if !offline.is_empty() {
+                        text.push_str(&format!("  offline {}", offline));
+                    }
+                    rows.push(("CPU cores".to_string(), text));
+                }
+
+                rows.extend(mode.clocks.iter().map(|clock| {
+                    let limit = match (clock.min_frequency, clock.max_frequency) {
+                        (Some(min), Some(max)) => format!("{} - {} MHz", min, max),
+                        (None, Some(max)) => format!("up to {} MHz", max),
+                        (Some(min), None) => format!("from {} MHz", min),
+                        (None, None) => "no limit".to_string(),
+                    };
+                    (clock.name.clone(), limit)
+                }));
+
+                rows.extend(mode.settings.iter().cloned());
+
+                let width = rows
+                    .iter()
+                    .map(|(name, _)| name.len())
+                    .max()
+                    .unwrap_or_default();
+
+                let mut lines = vec![
+                    Line::from(format!(
+                        "{} (id {}){}",
+                        mode.name,
+                        mode.id,
+                        if *current { ", current" } else { "" }
+                    ))
+                    .bold(),
+                    Line::default(),
+                ];
+                lines.extend(
+                    rows.into_iter()
+                        .map(|(name, value)| Line::from(format!("{:width$}  {}", name, value))),
+                );
+                lines
+            }
+            Popup::Running { text } => vec![
+                Line::from(format!("{}...", text)),
+                Line::default(),
+                Line::from("q: quit").dim(),
+            ],
+            Popup::Message { text, .. } => vec![
+                Line::from(text.as_str()),
+                Line::default(),
+                Line::from("Press any key").dim(),
+            ],
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        let lines = self.lines();
+
+        let width = lines
+            .iter()
+            .map(Line::width)
+            .max()
+            .unwrap_or_default()
+            .max(self.title().len()) as u16
+            + 4;
+        // Long messages wrap
+        let width = width
+            .min(72)
+            .min(frame.area().width.saturating_sub(4))
+            .max(20);
+        let inner_width = (width as usize).saturating_sub(4).max(1);
+        let height = lines
+            .iter()
+            .map(|line| line.width().div_ceil(inner_width).max(1))
+            .sum::<usize>() as u16
+            + 2;
+
+        let [area] = Layout::horizontal([Constraint::Length(width)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let border_style = match self {
+            Popup::Message { error: true, .. } => Style::new().fg(Color::Red),
+            _ => Style::new(),
+        };
+
+        let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .title(self.title())
+                .title_style(Style::new().bold())
+                .border_style(border_style)
+                .padding(Padding::horizontal(1))
+                .borders(Borders::ALL),
+        );
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
+    }
+}
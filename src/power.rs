@@ -1,10 +1,13 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use log::error;
 use std::{
     fs::{self, File},
-    io::{Read, Seek},
+    io::{Read, Seek, Write},
+    process::{Command, Stdio},
+    sync::LazyLock,
 };
 
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
@@ -13,6 +16,15 @@ use ratatui::{
 };
 use regex::Regex;
 
+use crate::{collector::Collector, root};
+#[cfg(feature = "tui")]
+use crate::{config::Config, history::Series};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// 15W, MODE_15W_6CORE, 30W_ALL... MAXN has no budget
+static POWER_BUDGET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)W").unwrap());
+
 #[derive(Debug, Default)]
 pub struct Power {
     pub channels: Vec<Channel>,
@@ -23,21 +35,55 @@ pub struct Power {
 pub struct Channel {
     current_file: File,
     voltage_file: File,
-    name: String,
-    power: f32,
+    pub name: String,
+    pub power: f32,
 }
 
 #[derive(Debug, Default)]
 pub struct NVPMode {
     file: Option<File>,
-    mode: Option<Mode>,
-    available_modes: Vec<Mode>,
+    pub mode: Option<Mode>,
+    pub available_modes: Vec<Mode>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Mode {
-    id: u8,
-    name: String,
+    pub id: u8,
+    pub name: String,
+    /// Watts, from the name of the mode, none for MAXN
+    #[serde(default)]
+    pub power_budget: Option<u32>,
+    /// Cores set online or offline
+    #[serde(default)]
+    pub cpu_online: Vec<(usize, bool)>,
+    /// Frequency limits of the CPU clusters, GPU, EMC, DLA and PVA
+    #[serde(default)]
+    pub clocks: Vec<ClockLimit>,
+    /// The other parameters, e.g. `GPU_POWER_CONTROL_ENABLE GPU_PWR_CNTL_EN` = `on`
+    #[serde(default)]
+    pub settings: Vec<(String, String)>,
+}
+
+/// MHz, none when the clock is not limited
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClockLimit {
+    pub name: String,
+    pub min_frequency: Option<u64>,
+    pub max_frequency: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PowerSnapshot {
+    pub channels: Vec<ChannelSnapshot>,
+    pub mode: Option<Mode>,
+    pub available_modes: Vec<Mode>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelSnapshot {
+    pub name: String,
+    /// mW
+    pub power: f32,
 }
 
 impl Channel {
@@ -59,67 +105,162 @@ impl Channel {
     }
 }
 
-impl NVPMode {
-    fn new() -> Result<Option<Self>> {
-        let mut file =
-            File::open("/etc/nvpmodel.conf").context("Failed to open /etc/nvpmodel.conf")?;
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer)?;
-
-        let mut modes: Vec<Mode> = Vec::new();
-        let pattern = r#"< POWER_MODEL ID=(\d+) NAME=(\d+)W >"#;
+impl Mode {
+    /// The settings of a `< POWER_MODEL ID=<id> NAME=<name> >` section
+    fn new(id: u8, name: &str) -> Self {
+        let power_budget = POWER_BUDGET
+            .captures(name)
+            .and_then(|captures| captures[1].parse::<u32>().ok());
+
+        Self {
+            id,
+            name: name.to_string(),
+            power_budget,
+            ..Self::default()
+        }
+    }
 
-        let regex = Regex::new(pattern)?;
+    /// Applies a `<PARAM> <KEY> <VALUE>` line of the mode
+    fn set(&mut self, param: &str, key: &str, value: &str) -> Result<()> {
+        match (param, key) {
+            ("CPU_ONLINE", core) => {
+                let core = core
+                    .strip_prefix("CORE_")
+                    .and_then(|core| core.parse::<usize>().ok())
+                    .context(format!("Invalid core {} in nvpmodel.conf", core))?;
+                self.cpu_online.push((core, value == "1"));
+            }
+            (name, "MIN_FREQ" | "MAX_FREQ") => {
+                let frequency = value
+                    .parse::<i64>()
+                    .context(format!("Invalid frequency {} in nvpmodel.conf", value))?;
+                // The CPU frequencies are in kHz, the others in Hz
+                let frequency = match frequency {
+                    // -1 is the maximum and 0 no limit
+                    ..=0 => None,
+                    _ if name.starts_with("CPU") => Some(frequency as u64 / 1000),
+                    _ => Some(frequency as u64 / 1_000_000),
+                };
+
+                let clock = match self.clocks.iter_mut().find(|clock| clock.name == name) {
+                    Some(clock) => clock,
+                    None => {
+                        self.clocks.push(ClockLimit {
+                            name: name.to_string(),
+                            ..ClockLimit::default()
+                        });
+                        self.clocks.last_mut().unwrap()
+                    }
+                };
+                if key == "MIN_FREQ" {
+                    clock.min_frequency = frequency;
+                } else {
+                    clock.max_frequency = frequency;
+                }
+            }
+            _ => self
+                .settings
+                .push((format!("{} {}", param, key), value.to_string())),
+        }
 
-        for line in buffer.lines() {
-            if let Some(captures) = regex.captures(line) {
-                let id = captures.get(1).unwrap().as_str().parse::<u8>()?;
-                let name = captures.get(2).unwrap().as_str().to_owned();
+        Ok(())
+    }
+}
 
-                let mode = Mode { id, name };
+/// Parses the power modes and the default mode id of nvpmodel.conf
+fn parse_conf(conf: &str) -> Result<(Vec<Mode>, Option<u8>)> {
+    let mut modes: Vec<Mode> = Vec::new();
+    let mut default = None;
+    // Only the lines of a POWER_MODEL section belong to a mode, not the PARAM definitions
+    let mut in_mode = false;
+
+    for line in conf.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
 
-                modes.push(mode);
+        if let Some(section) = line
+            .strip_prefix('<')
+            .and_then(|line| line.strip_suffix('>'))
+        {
+            let mut tokens = section.split_whitespace();
+            let kind = tokens.next().unwrap_or_default();
+            let attributes: HashMap<&str, &str> =
+                tokens.filter_map(|token| token.split_once('=')).collect();
+
+            in_mode = false;
+            match kind {
+                "POWER_MODEL" => {
+                    let id = attributes
+                        .get("ID")
+                        .and_then(|id| id.parse::<u8>().ok())
+                        .context(format!("Invalid power model {} in nvpmodel.conf", line))?;
+                    let name = attributes.get("NAME").copied().unwrap_or_default();
+                    modes.push(Mode::new(id, name));
+                    in_mode = true;
+                }
+                "PM_CONFIG" => {
+                    default = attributes
+                        .get("DEFAULT")
+                        .and_then(|id| id.parse::<u8>().ok());
+                }
+                _ => {}
             }
+            continue;
         }
 
-        // Current mode if active
+        if in_mode
+            && let Some(mode) = modes.last_mut()
+            && let [param, key, value] = line.split_whitespace().collect::<Vec<&str>>()[..]
+            // A bad setting only loses that line, not the modes
+            && let Err(e) = mode.set(param, key, value)
+        {
+            error!("{}, skipping it", e);
+        }
+    }
 
-        if let Ok(mut fd) = File::open("/var/lib/nvpmodel/status") {
-            let mut buffer = String::new();
-            fd.read_to_string(&mut buffer)?;
+    Ok((modes, default))
+}
 
-            let parts: Vec<&str> = buffer.split(':').collect();
-            let current_mode_id = parts[1].parse::<u8>()?;
+/// Reads the mode id of `/var/lib/nvpmodel/status`, e.g. `pmode:0002 fmode:quiet`
+fn parse_status(status: &str) -> Result<u8> {
+    status
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("pmode:"))
+        .and_then(|id| id.parse::<u8>().ok())
+        .context(format!("Invalid nvpmodel status {}", status.trim()))
+}
 
-            if let Some(mode) = modes.iter().find(|&mode| mode.id == current_mode_id) {
-                return Ok(Some(Self {
-                    file: Some(fd),
-                    mode: Some(mode.clone()),
-                    available_modes: modes,
-                }));
-            }
-        }
+impl NVPMode {
+    fn new() -> Result<Option<Self>> {
+        let conf_path = root::path("/etc/nvpmodel.conf");
+        let conf = fs::read_to_string(&conf_path)
+            .context(format!("Failed to read {}", conf_path.display()))?;
 
-        // Default mode
-        let pattern = r#"< PM_CONFIG DEFAULT=(\d+) >"#;
+        let (modes, default) =
+            parse_conf(&conf).context(format!("Failed to parse {}", conf_path.display()))?;
 
-        let regex = Regex::new(pattern)?;
+        let mut nvpmode = Self {
+            file: File::open(root::path("/var/lib/nvpmodel/status")).ok(),
+            mode: None,
+            available_modes: modes,
+        };
 
-        for line in buffer.lines() {
-            if let Some(captures) = regex.captures(line) {
-                let default_id = captures.get(1).unwrap().as_str().parse::<u8>().unwrap();
+        // The default mode until nvpmodel has written its status
+        if nvpmode.refresh().is_err() || nvpmode.mode.is_none() {
+            nvpmode.mode = nvpmode
+                .available_modes
+                .iter()
+                .find(|mode| Some(mode.id) == default)
+                .cloned();
+        }
 
-                if let Some(mode) = modes.iter().find(|&mode| mode.id == default_id) {
-                    return Ok(Some(Self {
-                        file: None,
-                        mode: Some(mode.clone()),
-                        available_modes: modes,
-                    }));
-                }
-            }
+        if nvpmode.mode.is_none() && nvpmode.available_modes.is_empty() {
+            return Ok(None);
         }
 
-        Ok(None)
+        Ok(Some(nvpmode))
     }
 
     fn refresh(&mut self) -> Result<()> {
@@ -128,8 +269,7 @@ impl NVPMode {
             let mut buffer = String::new();
             fd.read_to_string(&mut buffer)?;
 
-            let parts: Vec<&str> = buffer.split(':').collect();
-            let current_mode_id = parts[1].parse::<u8>()?;
+            let current_mode_id = parse_status(&buffer)?;
 
             if let Some(mode) = self
                 .available_modes
@@ -143,10 +283,64 @@ impl NVPMode {
     }
 }
 
+/// Result of `nvpmodel -m`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeChange {
+    Done,
+    /// The mode needs a reboot, which was declined
+    RebootRequired,
+}
+
+/// Printed by nvpmodel before asking to reboot, followed by the mode id
+const REBOOT_PROMPT: &str = "Reboot required for changing to this power mode";
+
+/// Switches the power mode with `<command> -m <id>`. nvpmodel asks before rebooting when the mode
+/// needs it, `reboot` answers yes.
+pub fn set_mode(command: &str, id: u8, reboot: bool) -> Result<ModeChange> {
+    let mut child = Command::new(command)
+        .arg("-m")
+        .arg(id.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context(format!("Failed to run {}", command))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // nvpmodel doesn't read the answer when no reboot is needed
+        let _ = stdin.write_all(if reboot { b"YES\n" } else { b"NO\n" });
+    }
+
+    let output = child.wait_with_output()?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    if !reboot && text.contains(REBOOT_PROMPT) {
+        return Ok(ModeChange::RebootRequired);
+    }
+
+    if !output.status.success() {
+        bail!(
+            "{} -m {} failed: {}",
+            command,
+            id,
+            text.lines().last().unwrap_or_default().trim()
+        );
+    }
+
+    Ok(ModeChange::Done)
+}
+
 impl Power {
-    pub fn init() -> Result<Vec<Channel>> {
-        let hwmon_dir_content = fs::read_dir("/sys/class/hwmon/")
-            .context("Failed to read from the directory /sys/class/hwmon")?;
+    pub fn channels() -> Result<Vec<Channel>> {
+        let hwmon_path = root::path("/sys/class/hwmon/");
+        let hwmon_dir_content = fs::read_dir(&hwmon_path).context(format!(
+            "Failed to read from the directory {}",
+            hwmon_path.display()
+        ))?;
 
         let channel_regex = Regex::new(r"in(\d)_label")?;
 
@@ -171,7 +365,7 @@ impl Power {
                         let channel_index = channel_index.as_str();
 
                         let channel_name = match fs::read_to_string(&path) {
-                            Ok(v) => v,
+                            Ok(v) => v.trim().to_string(),
                             Err(_) => continue,
                         };
 
@@ -209,8 +403,12 @@ impl Power {
         }
         Ok(channels)
     }
+}
 
-    pub fn new() -> Self {
+impl Collector for Power {
+    type Snapshot = PowerSnapshot;
+
+    fn init() -> Result<Self> {
         let nvpmode = match NVPMode::new() {
             Ok(mode) => mode,
             Err(e) => {
@@ -219,7 +417,7 @@ impl Power {
             }
         };
 
-        let channels = match Power::init() {
+        let channels = match Power::channels() {
             Ok(channels) => channels,
             Err(e) => {
                 error!("{}", e);
@@ -227,10 +425,10 @@ impl Power {
             }
         };
 
-        Self { channels, nvpmode }
+        Ok(Self { channels, nvpmode })
     }
 
-    pub fn refresh(&mut self) {
+    fn refresh(&mut self) {
         if let Some(nvpmode) = &mut self.nvpmode
             && let Err(e) = nvpmode.refresh()
         {
@@ -244,9 +442,63 @@ impl Power {
         }
     }
 
-    pub fn render(&self, frame: &mut Frame, block: Rect) {
+    fn snapshot(&self) -> PowerSnapshot {
+        PowerSnapshot {
+            channels: self
+                .channels
+                .iter()
+                .map(|channel| ChannelSnapshot {
+                    name: channel.name.clone(),
+                    power: channel.power,
+                })
+                .collect(),
+            mode: self
+                .nvpmode
+                .as_ref()
+                .and_then(|nvpmode| nvpmode.mode.clone()),
+            available_modes: self
+                .nvpmode
+                .as_ref()
+                .map(|nvpmode| nvpmode.available_modes.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl PowerSnapshot {
+    /// The rails that are not hidden in the configuration
+    pub fn visible<'a>(&'a self, config: &'a Config) -> impl Iterator<Item = &'a ChannelSnapshot> {
+        self.channels
+            .iter()
+            .filter(|channel| !config.power.is_hidden(&channel.name))
+    }
+
+    pub fn render(
+        &self,
+        frame: &mut Frame,
+        block: Rect,
+        history: &HashMap<String, Series>,
+        config: &Config,
+    ) {
+        // Only the rails with a threshold in the configuration are colored
+        let level = |channel: &ChannelSnapshot| {
+            config
+                .thresholds
+                .power
+                .get(&channel.name)
+                .map(|threshold| threshold.level(channel.power as f64))
+        };
+
         let container = Block::default()
             .borders(Borders::ALL)
+            .border_style(
+                self.visible(config)
+                    .filter_map(level)
+                    .max()
+                    .unwrap_or_default()
+                    .border_style(),
+            )
             .title("Power")
             .padding(Padding::horizontal(1))
             .title_style(Style::new().bold());
@@ -265,35 +517,63 @@ impl Power {
         // nvpmodel
         let rows = [Row::new(vec![
             Cell::new("Mode").style(Style::default().bold()),
-            Cell::new(match &self.nvpmode {
-                Some(nvpmode) => match &nvpmode.mode {
-                    Some(mode) => format!("{}W", &mode.name),
-                    None => " - ".to_string(),
-                },
+            Cell::new(match &self.mode {
+                Some(mode) => mode.name.clone(),
                 None => " - ".to_string(),
             }),
         ])];
 
-        let widths = [Constraint::Length(16), Constraint::Length(7)];
+        let widths = [Constraint::Length(16), Constraint::Min(7)];
 
         let nvpmodel = Table::new(rows, widths).block(Block::default());
 
         // Power consumption
+        let name_width = self
+            .visible(config)
+            .map(|channel| config.power.name(&channel.name).chars().count())
+            .max()
+            .unwrap_or_default()
+            .max(16) as u16;
+
+        let history_width = power_consumption_block
+            .width
+            .saturating_sub(name_width + 1 + 9 + 1);
+
         let rows: Vec<Row> = self
-            .channels
-            .iter()
+            .visible(config)
             .map(|channel| {
+                let sparkline = history
+                    .get(&channel.name)
+                    .map(|series| {
+                        series.sparkline(
+                            history_width as usize,
+                            0.0,
+                            series.max().unwrap_or_default(),
+                        )
+                    })
+                    .unwrap_or_default();
+
+                let power = Cell::new(format!("{} mW", channel.power));
+
                 Row::new(vec![
-                    channel.name.to_owned(),
-                    format!("{} mW", channel.power),
+                    Cell::new(config.power.name(&channel.name).to_string()),
+                    match level(channel) {
+                        Some(level) => power.style(level.style()),
+                        None => power,
+                    },
+                    Cell::new(sparkline),
                 ])
             })
             .collect();
 
-        let widths = [Constraint::Length(16), Constraint::Length(7)];
+        let widths = [
+            Constraint::Length(name_width),
+            Constraint::Length(9),
+            Constraint::Length(history_width),
+        ];
 
         let power = Table::new(rows, widths)
-            .header(Row::new(vec!["Channel", "Power"]).style(Style::new().bold()))
+            .header(Row::new(vec!["Channel", "Power", "History"]).style(Style::new().bold()))
             .block(Block::default());
 
         frame.render_widget(container, block);
@@ -301,3 +581,36 @@ impl Power {
         frame.render_widget(power, power_consumption_block);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_conf_skips_bad_lines() {
+        let conf = "
+< POWER_MODEL ID=0 NAME=MAXN >
+CPU_ONLINE CORE_X 1
+CPU_ONLINE CORE_1 1
+GPU MAX_FREQ fast
+GPU MIN_FREQ 306000000
+< POWER_MODEL ID=1 NAME=MODE_15W >
+CPU_ONLINE CORE_1 0
+< PM_CONFIG DEFAULT=1 >
+";
+        let (modes, default) = parse_conf(conf).unwrap();
+
+        assert_eq!(default, Some(1));
+        assert_eq!(modes.len(), 2);
+        assert_eq!(modes[0].cpu_online, [(1, true)]);
+        assert_eq!(
+            modes[0].clocks,
+            [ClockLimit {
+                name: "GPU".to_string(),
+                min_frequency: Some(306),
+                max_frequency: None,
+            }]
+        );
+        assert_eq!(modes[1].cpu_online, [(1, false)]);
+    }
+}
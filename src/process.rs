@@ -0,0 +1,625 @@
+use std::{
+    collections::HashMap,
+    fs, io, mem,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::error;
+#[cfg(feature = "tui")]
+use ratatui::{
+    Frame,
+    layout::{Constraint, Rect},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Borders, Cell, Padding, Row, Table, TableState},
+};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tui")]
+use strum::IntoEnumIterator;
+#[cfg(feature = "tui")]
+use strum_macros::{Display, EnumIter};
+
+use crate::{collector::Collector, device, nvmap::Nvmap, root};
+
+/// The processes of `/proc`, with their CPU usage since the previous refresh.
+#[derive(Debug)]
+pub struct Processes {
+    /// Clock ticks per second of the utime and stime fields
+    ticks_per_second: f64,
+    users: HashMap<u32, String>,
+    /// The nvmap accounting is readable
+    nvmap: bool,
+    /// CPU ticks of each pid at the previous refresh
+    ticks: HashMap<u32, u64>,
+    last_refresh: Instant,
+    /// Engines opened by each pid when already scanned for this refresh, e.g. by the Engine collector
+    pub devices: Option<HashMap<u32, Vec<&'static str>>>,
+    pub processes: Vec<ProcessSnapshot>,
+}
+
+impl Default for Processes {
+    fn default() -> Self {
+        Self {
+            ticks_per_second: 100.0,
+            users: HashMap::new(),
+            nvmap: false,
+            ticks: HashMap::new(),
+            last_refresh: Instant::now(),
+            devices: None,
+            processes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProcessesSnapshot {
+    pub processes: Vec<ProcessSnapshot>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProcessSnapshot {
+    pub pid: u32,
+    pub user: String,
+    /// Percentage of one core
+    pub cpu: f64,
+    /// Resident memory in KiB
+    pub rss: u64,
+    /// KiB mapped for the GPU and the other engines, none without the nvmap accounting
+    pub gpu: Option<u64>,
+    pub threads: u32,
+    /// -20 to 19
+    pub nice: i32,
+    /// R, S, D, Z, T...
+    pub state: char,
+    /// Engines of the device nodes the process has open, e.g. GPU or NVENC
+    pub accelerators: Vec<String>,
+    /// Command line, or the name between brackets for the kernel threads
+    pub command: String,
+}
+
+/// Fields of `/proc/<pid>/stat` and `/proc/<pid>/status`
+struct Stat {
+    name: String,
+    state: char,
+    /// utime + stime
+    ticks: u64,
+    nice: i32,
+    uid: u32,
+    rss: u64,
+    threads: u32,
+}
+
+impl Processes {
+    /// Maps the uids of `/etc/passwd` to the user names
+    fn read_users() -> HashMap<u32, String> {
+        let path = root::path("/etc/passwd");
+
+        fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let uid = fields.nth(1)?.parse::<u32>().ok()?;
+                Some((uid, name.to_string()))
+            })
+            .collect()
+    }
+
+    fn read_stat(dir: &Path) -> Result<Stat> {
+        let path = dir.join("stat");
+        let stat =
+            fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+
+        // The name can contain spaces and parentheses, the other fields follow the last one
+        let (start, end) = stat
+            .find('(')
+            .zip(stat.rfind(')'))
+            .ok_or(anyhow!("Invalid {}", path.display()))?;
+        let name = stat[start + 1..end].to_string();
+        let fields: Vec<&str> = stat[end + 1..].split_whitespace().collect();
+
+        let state = fields
+            .first()
+            .and_then(|state| state.chars().next())
+            .ok_or(anyhow!("Invalid {}", path.display()))?;
+        // Fields 14, 15 and 19 of proc(5), counted from the state which is the 3rd
+        let utime: u64 = fields.get(11).unwrap_or(&"0").parse()?;
+        let stime: u64 = fields.get(12).unwrap_or(&"0").parse()?;
+        let nice: i32 = fields.get(16).unwrap_or(&"0").parse()?;
+
+        let path = dir.join("status");
+        let status =
+            fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+
+        let field = |key: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(key))
+                .and_then(|value| value.split_whitespace().next())
+                .and_then(|value| value.parse().ok())
+        };
+
+        Ok(Stat {
+            name,
+            state,
+            ticks: utime + stime,
+            nice,
+            uid: field("Uid:").unwrap_or_default() as u32,
+            rss: field("VmRSS:").unwrap_or_default(),
+            threads: field("Threads:").unwrap_or(1) as u32,
+        })
+    }
+
+    fn read_command(dir: &Path, name: &str) -> String {
+        let cmdline = fs::read(dir.join("cmdline")).unwrap_or_default();
+
+        let command = String::from_utf8_lossy(&cmdline)
+            .split('\0')
+            .filter(|arg| !arg.is_empty())
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        if command.is_empty() {
+            format!("[{}]", name)
+        } else {
+            command
+        }
+    }
+}
+
+impl Collector for Processes {
+    type Snapshot = ProcessesSnapshot;
+
+    fn init() -> Result<Self> {
+        let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+
+        Ok(Self {
+            ticks_per_second: if ticks_per_second > 0 {
+                ticks_per_second as f64
+            } else {
+                100.0
+            },
+            users: Self::read_users(),
+            nvmap: Nvmap::read().is_ok(),
+            ..Self::default()
+        })
+    }
+
+    fn refresh(&mut self) {
+        let elapsed = self.last_refresh.elapsed().as_secs_f64();
+        self.last_refresh = Instant::now();
+
+        let entries = match fs::read_dir(root::path("/proc")) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("Failed to read /proc: {}", e);
+                return;
+            }
+        };
+
+        let nvmap = if self.nvmap {
+            Nvmap::read().map_or_else(
+                |e| {
+                    error!("{}", e);
+                    None
+                },
+                Some,
+            )
+        } else {
+            None
+        };
+
+        let mut accelerators = self.devices.take().unwrap_or_else(device::scan);
+        let mut ticks = HashMap::new();
+        let mut processes = Vec::new();
+
+        for entry in entries.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|pid| pid.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let dir: PathBuf = entry.path();
+
+            // The process may have exited since the directory was listed
+            let Ok(stat) = Self::read_stat(&dir) else {
+                continue;
+            };
+
+            let cpu = match self.ticks.get(&pid) {
+                Some(previous) if elapsed > 0.0 => {
+                    stat.ticks.saturating_sub(*previous) as f64 / self.ticks_per_second / elapsed
+                        * 100.0
+                }
+                _ => 0.0,
+            };
+            ticks.insert(pid, stat.ticks);
+
+            processes.push(ProcessSnapshot {
+                pid,
+                user: self
+                    .users
+                    .get(&stat.uid)
+                    .cloned()
+                    .unwrap_or(stat.uid.to_string()),
+                cpu,
+                rss: stat.rss,
+                gpu: nvmap
+                    .as_ref()
+                    .map(|nvmap| nvmap.clients.get(&pid).copied().unwrap_or_default()),
+                threads: stat.threads,
+                nice: stat.nice,
+                state: stat.state,
+                accelerators: accelerators
+                    .remove(&pid)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                command: Self::read_command(&dir, &stat.name),
+            });
+        }
+
+        self.ticks = ticks;
+        self.processes = processes;
+    }
+
+    fn snapshot(&self) -> ProcessesSnapshot {
+        ProcessesSnapshot {
+            processes: self.processes.clone(),
+        }
+    }
+}
+
+/// Sends a signal to a process, e.g. `libc::SIGTERM`
+pub fn kill(pid: u32, signal: i32) -> Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        return Err(io::Error::last_os_error())
+            .context(format!("Failed to send signal {} to {}", signal, pid));
+    }
+    Ok(())
+}
+
+/// Sets the nice value of a process, from -20 to 19
+pub fn renice(pid: u32, nice: i32) -> Result<()> {
+    if !(-20..=19).contains(&nice) {
+        bail!("Invalid nice value {}, expected -20 to 19", nice);
+    }
+
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, nice) } != 0 {
+        return Err(io::Error::last_os_error()).context(format!(
+            "Failed to set the nice value of {} to {}",
+            pid, nice
+        ));
+    }
+    Ok(())
+}
+
+/// Cores a process may run on
+pub fn affinity(pid: u32) -> Result<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+
+    let result = unsafe {
+        libc::sched_getaffinity(
+            pid as libc::pid_t,
+            mem::size_of::<libc::cpu_set_t>(),
+            &mut set,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error())
+            .context(format!("Failed to get the CPU affinity of {}", pid));
+    }
+
+    Ok((0..libc::CPU_SETSIZE as usize)
+        .filter(|core| unsafe { libc::CPU_ISSET(*core, &set) })
+        .collect())
+}
+
+/// Restricts a process to some cores
+pub fn set_affinity(pid: u32, cores: &[usize]) -> Result<()> {
+    if cores.is_empty() {
+        bail!("At least one core is needed");
+    }
+
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    for core in cores {
+        unsafe { libc::CPU_SET(*core, &mut set) };
+    }
+
+    let result = unsafe {
+        libc::sched_setaffinity(pid as libc::pid_t, mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error())
+            .context(format!("Failed to set the CPU affinity of {}", pid));
+    }
+    Ok(())
+}
+
+/// Formats a size in KiB
+#[cfg(feature = "tui")]
+fn format_size(kib: u64) -> String {
+    match kib {
+        0..1024 => format!("{}K", kib),
+        1024..1_048_576 => format!("{:.1}M", kib as f64 / 1024.0),
+        _ => format!("{:.1}G", kib as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, EnumIter)]
+pub enum Column {
+    #[strum(serialize = "PID")]
+    Pid,
+    #[strum(serialize = "User")]
+    User,
+    #[default]
+    #[strum(serialize = "CPU%")]
+    Cpu,
+    #[strum(serialize = "RSS")]
+    Rss,
+    #[strum(serialize = "GPU")]
+    Gpu,
+    #[strum(serialize = "Threads")]
+    Threads,
+    #[strum(serialize = "NI")]
+    Nice,
+    #[strum(serialize = "State")]
+    State,
+    #[strum(serialize = "Accelerators")]
+    Accelerators,
+    #[strum(serialize = "Command")]
+    Command,
+}
+
+#[cfg(feature = "tui")]
+impl Column {
+    fn width(&self) -> Constraint {
+        match self {
+            Column::Pid => Constraint::Length(7),
+            Column::User => Constraint::Length(10),
+            Column::Cpu => Constraint::Length(6),
+            Column::Rss => Constraint::Length(7),
+            Column::Gpu => Constraint::Length(7),
+            Column::Threads => Constraint::Length(7),
+            Column::Nice => Constraint::Length(3),
+            Column::State => Constraint::Length(5),
+            Column::Accelerators => Constraint::Length(12),
+            Column::Command => Constraint::Min(10),
+        }
+    }
+
+    fn compare(&self, a: &ProcessSnapshot, b: &ProcessSnapshot) -> std::cmp::Ordering {
+        match self {
+            Column::Pid => a.pid.cmp(&b.pid),
+            Column::User => a.user.cmp(&b.user),
+            Column::Cpu => a.cpu.total_cmp(&b.cpu),
+            Column::Rss => a.rss.cmp(&b.rss),
+            Column::Gpu => a.gpu.cmp(&b.gpu),
+            Column::Threads => a.threads.cmp(&b.threads),
+            Column::Nice => a.nice.cmp(&b.nice),
+            Column::State => a.state.cmp(&b.state),
+            Column::Accelerators => a.accelerators.cmp(&b.accelerators),
+            Column::Command => a.command.cmp(&b.command),
+        }
+    }
+
+    fn cell(&self, process: &ProcessSnapshot) -> String {
+        match self {
+            Column::Pid => process.pid.to_string(),
+            Column::User => process.user.clone(),
+            Column::Cpu => format!("{:.1}", process.cpu),
+            Column::Rss => format_size(process.rss),
+            Column::Gpu => match process.gpu {
+                Some(gpu) if gpu > 0 => format_size(gpu),
+                _ => "-".to_string(),
+            },
+            Column::Threads => process.threads.to_string(),
+            Column::Nice => process.nice.to_string(),
+            Column::State => process.state.to_string(),
+            Column::Accelerators => process.accelerators.join(","),
+            Column::Command => process.command.clone(),
+        }
+    }
+
+    /// The numbers are right aligned and sorted in descending order first
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Column::Pid | Column::Cpu | Column::Rss | Column::Gpu | Column::Threads | Column::Nice
+        )
+    }
+}
+
+/// Full screen table of the processes, sorted by a column and filtered by command.
+#[cfg(feature = "tui")]
+#[derive(Debug, Default)]
+pub struct ProcessView {
+    sort: Column,
+    ascending: bool,
+    pub filter: String,
+    /// The filter is being typed
+    pub editing: bool,
+    /// Pid of the selected row, kept when the rows move
+    selected: Option<u32>,
+    state: TableState,
+    /// Number of rows shown at the last render, for paging
+    page: usize,
+}
+
+#[cfg(feature = "tui")]
+impl ProcessView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Processes matching the filter, in the sort order
+    pub fn rows<'a>(&self, processes: &'a [ProcessSnapshot]) -> Vec<&'a ProcessSnapshot> {
+        let filter = self.filter.to_lowercase();
+
+        let mut rows: Vec<&ProcessSnapshot> = processes
+            .iter()
+            .filter(|process| process.command.to_lowercase().contains(&filter))
+            .collect();
+
+        rows.sort_by(|a, b| {
+            let ordering = self.sort.compare(a, b).then(a.pid.cmp(&b.pid));
+            if self.ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+
+        rows
+    }
+
+    /// The selected process, the first row until one is selected
+    pub fn selected<'a>(&self, processes: &'a [ProcessSnapshot]) -> Option<&'a ProcessSnapshot> {
+        let rows = self.rows(processes);
+        rows.get(self.index(&rows)).copied()
+    }
+
+    fn index(&self, rows: &[&ProcessSnapshot]) -> usize {
+        self.selected
+            .and_then(|pid| rows.iter().position(|process| process.pid == pid))
+            .unwrap_or_default()
+    }
+
+    /// Moves the selection by `offset` rows
+    pub fn scroll(&mut self, processes: &[ProcessSnapshot], offset: isize) {
+        let rows = self.rows(processes);
+        if rows.is_empty() {
+            return;
+        }
+
+        let index = self
+            .index(&rows)
+            .saturating_add_signed(offset)
+            .min(rows.len() - 1);
+        self.selected = Some(rows[index].pid);
+    }
+
+    pub fn page_down(&mut self, processes: &[ProcessSnapshot]) {
+        self.scroll(processes, self.page.max(1) as isize);
+    }
+
+    pub fn page_up(&mut self, processes: &[ProcessSnapshot]) {
+        self.scroll(processes, -(self.page.max(1) as isize));
+    }
+
+    pub fn first(&mut self, processes: &[ProcessSnapshot]) {
+        self.selected = self.rows(processes).first().map(|process| process.pid);
+    }
+
+    pub fn last(&mut self, processes: &[ProcessSnapshot]) {
+        self.selected = self.rows(processes).last().map(|process| process.pid);
+    }
+
+    /// Sorts by the next column, or the previous one when `offset` is negative
+    pub fn sort_by_next(&mut self, offset: isize) {
+        let columns: Vec<Column> = Column::iter().collect();
+        let index = columns
+            .iter()
+            .position(|column| *column == self.sort)
+            .unwrap();
+        self.sort_by(
+            columns[(index as isize + offset).rem_euclid(columns.len() as isize) as usize],
+        );
+    }
+
+    pub fn sort_by(&mut self, column: Column) {
+        self.sort = column;
+        self.ascending = !column.is_numeric();
+    }
+
+    pub fn reverse(&mut self) {
+        self.ascending = !self.ascending;
+    }
+
+    pub fn start_filter(&mut self) {
+        self.editing = true;
+    }
+
+    /// Stops typing the filter, it stays applied
+    pub fn apply_filter(&mut self) {
+        self.editing = false;
+    }
+
+    pub fn clear_filter(&mut self) {
+        self.editing = false;
+        self.filter.clear();
+    }
+
+    pub fn push_filter(&mut self, c: char) {
+        self.filter.push(c);
+    }
+
+    pub fn pop_filter(&mut self) {
+        self.filter.pop();
+    }
+
+    pub fn render(&mut self, processes: &[ProcessSnapshot], frame: &mut Frame, block: Rect) {
+        let rows = self.rows(processes);
+        let index = self.index(&rows);
+        // Falls back to the first row when the selected process is gone
+        self.selected = rows.get(index).map(|process| process.pid);
+        self.state.select(self.selected.map(|_| index));
+        // Borders and header
+        self.page = block.height.saturating_sub(3) as usize;
+
+        let header = Row::new(Column::iter().map(|column| {
+            let mut title = column.to_string();
+            if column == self.sort {
+                title.push(if self.ascending { '▲' } else { '▼' });
+            }
+            let cell = if column.is_numeric() {
+                Line::from(title).right_aligned()
+            } else {
+                Line::from(title)
+            };
+            Cell::from(cell)
+        }))
+        .style(Style::new().bold());
+
+        let table_rows = rows.iter().map(|process| {
+            Row::new(Column::iter().map(|column| {
+                let text = column.cell(process);
+                if column.is_numeric() {
+                    Cell::from(Line::from(text).right_aligned())
+                } else {
+                    Cell::from(text)
+                }
+            }))
+        });
+
+        let filter = if self.editing {
+            format!(" Filter: {}_ ", self.filter)
+        } else if !self.filter.is_empty() {
+            format!(" Filter: {} ", self.filter)
+        } else {
+            " /: filter  </>: sort  r: reverse ".to_string()
+        };
+
+        let table = Table::new(table_rows, Column::iter().map(|column| column.width()))
+            .header(header)
+            .column_spacing(1)
+            .row_highlight_style(Style::new().reversed())
+            .block(
+                Block::default()
+                    .title(format!("Processes ({}/{})", rows.len(), processes.len()))
+                    .title_style(Style::new().bold())
+                    .title_bottom(filter)
+                    .padding(Padding::horizontal(1))
+                    .borders(Borders::ALL),
+            );
+
+        frame.render_stateful_widget(table, block, &mut self.state);
+    }
+}
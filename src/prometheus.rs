@@ -0,0 +1,320 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    process,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use log::{error, info};
+
+use crate::{
+    collector::{Collector, Collectors},
+    engine::HWState,
+    snapshot::Snapshot,
+    ticker::Ticker,
+};
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+/// Connections are answered one at a time, so a stalled client must not block the others
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Family<'a> {
+    name: &'a str,
+    help: &'a str,
+    samples: Vec<(Vec<(&'a str, String)>, f64)>,
+}
+
+impl<'a> Family<'a> {
+    fn new(name: &'a str, help: &'a str) -> Self {
+        Self {
+            name,
+            help,
+            samples: Vec::new(),
+        }
+    }
+
+    fn sample(mut self, labels: Vec<(&'a str, String)>, value: f64) -> Self {
+        self.samples.push((labels, value));
+        self
+    }
+
+    fn escape(value: &str) -> String {
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    }
+
+    fn write(&self, output: &mut String) {
+        if self.samples.is_empty() {
+            return;
+        }
+
+        let _ = writeln!(output, "# TYPE {} gauge", self.name);
+        let _ = writeln!(output, "# HELP {} {}", self.name, self.help);
+
+        for (labels, value) in &self.samples {
+            if labels.is_empty() {
+                let _ = writeln!(output, "{} {}", self.name, value);
+            } else {
+                let labels: Vec<String> = labels
+                    .iter()
+                    .map(|(key, value)| format!("{}=\"{}\"", key, Family::escape(value)))
+                    .collect();
+                let _ = writeln!(output, "{}{{{}}} {}", self.name, labels.join(","), value);
+            }
+        }
+    }
+}
+
+/// Renders all the metrics in the OpenMetrics text format.
+pub fn metrics(snapshot: &Snapshot) -> String {
+    let mut families: Vec<Family> = Vec::new();
+
+    let mut utilization = Family::new("tegra_cpu_utilization_percent", "CPU core utilization");
+    let mut frequency = Family::new("tegra_cpu_frequency_hertz", "CPU core frequency");
+    for core in &snapshot.cpu.cores {
+        utilization = utilization.sample(vec![("core", core.name.clone())], core.utilization);
+        if let Some(f) = core.frequency {
+            frequency = frequency.sample(vec![("core", core.name.clone())], f as f64 * 1e6);
+        }
+    }
+    families.push(utilization);
+    families.push(frequency);
+
+    let mut gpu_load = Family::new("tegra_gpu_load_percent", "GPU load");
+    if let Some(load) = snapshot.gpu.load {
+        gpu_load = gpu_load.sample(vec![], load);
+    }
+    families.push(gpu_load);
+
+    let mut gpu_frequency = Family::new("tegra_gpu_frequency_hertz", "GPU frequency");
+    let mut gpu_max_frequency = Family::new("tegra_gpu_max_frequency_hertz", "GPU max frequency");
+    if let Some(frequency) = snapshot.gpu.frequency {
+        gpu_frequency = gpu_frequency.sample(vec![], frequency as f64 * 1e6);
+    }
+    if let Some(max_frequency) = snapshot.gpu.max_frequency {
+        gpu_max_frequency = gpu_max_frequency.sample(vec![], max_frequency as f64 * 1e6);
+    }
+    families.push(gpu_frequency);
+    families.push(gpu_max_frequency);
+
+    let mut emc_frequency = Family::new("tegra_emc_frequency_hertz", "EMC frequency");
+    let mut emc_max_frequency = Family::new("tegra_emc_max_frequency_hertz", "EMC max frequency");
+    if let Some(emc) = &snapshot.memory.emc {
+        emc_frequency = emc_frequency.sample(vec![], emc.frequency as f64 * 1e6);
+        emc_max_frequency = emc_max_frequency.sample(vec![], emc.max_frequency as f64 * 1e6);
+    }
+    families.push(emc_frequency);
+    families.push(emc_max_frequency);
+
+    if let Some(mem) = &snapshot.memory.mem {
+        let mb = 1024.0 * 1024.0;
+        families.push(
+            Family::new("tegra_memory_ram_used_bytes", "Used RAM")
+                .sample(vec![], mem.used_ram * mb),
+        );
+        families.push(
+            Family::new("tegra_memory_ram_total_bytes", "Total RAM")
+                .sample(vec![], mem.total_ram * mb),
+        );
+        families.push(
+            Family::new("tegra_memory_swap_used_bytes", "Used swap")
+                .sample(vec![], mem.used_swap * mb),
+        );
+        families.push(
+            Family::new("tegra_memory_swap_total_bytes", "Total swap")
+                .sample(vec![], mem.total_swap * mb),
+        );
+    }
+
+    if let Some(gpu_shared) = snapshot.memory.gpu_shared {
+        families.push(
+            Family::new(
+                "tegra_memory_gpu_shared_bytes",
+                "RAM mapped for the GPU and the other engines",
+            )
+            .sample(vec![], gpu_shared * 1024.0 * 1024.0),
+        );
+    }
+
+    let mut power = Family::new("tegra_power_watts", "Power consumption of a rail");
+    for channel in &snapshot.power.channels {
+        power = power.sample(
+            vec![("rail", channel.name.clone())],
+            channel.power as f64 / 1000.0,
+        );
+    }
+    families.push(power);
+
+    let mut nvpmodel = Family::new("tegra_nvpmodel_mode", "Active nvpmodel power mode");
+    if let Some(mode) = &snapshot.power.mode {
+        nvpmodel = nvpmodel.sample(
+            vec![("id", mode.id.to_string()), ("name", mode.name.clone())],
+            1.0,
+        );
+    }
+    families.push(nvpmodel);
+
+    let mut temperature = Family::new("tegra_temperature_celsius", "Temperature of a thermal zone");
+    for sensor in &snapshot.thermal.sensors {
+        // Sensors below -25 C are not connected
+        if sensor.temperature < -25.0 {
+            continue;
+        }
+        temperature = temperature.sample(
+            vec![("zone", sensor.name.clone())],
+            sensor.temperature as f64,
+        );
+    }
+    families.push(temperature);
+
+    let mut fan_rpm = Family::new("tegra_fan_rpm", "Fan speed in revolutions per minute");
+    let mut fan_duty = Family::new("tegra_fan_duty_ratio", "PWM duty of the fan, from 0 to 1");
+    let mut fan_target = Family::new(
+        "tegra_fan_target_rpm",
+        "Fan speed of the nvfancontrol profile at the current temperature",
+    );
+    for fan in &snapshot.fan.fans {
+        let labels = || vec![("fan", fan.name.clone())];
+        if let Some(rpm) = fan.rpm {
+            fan_rpm = fan_rpm.sample(labels(), rpm as f64);
+        }
+        if let Some(pwm) = fan.pwm {
+            fan_duty = fan_duty.sample(labels(), pwm as f64 / 255.0);
+        }
+        if let Some(target_rpm) = fan.target_rpm {
+            fan_target = fan_target.sample(labels(), target_rpm as f64);
+        }
+    }
+    families.push(fan_rpm);
+    families.push(fan_duty);
+    families.push(fan_target);
+
+    let mut engine_running = Family::new("tegra_engine_running", "Whether the engine is running");
+    let mut engine_frequency = Family::new("tegra_engine_frequency_hertz", "Engine clock rate");
+    for hw in &snapshot.engine.hws {
+        let running = match hw.state {
+            HWState::Running => 1.0,
+            HWState::Idle => 0.0,
+        };
+        engine_running = engine_running.sample(vec![("engine", hw.name.to_string())], running);
+        engine_frequency =
+            engine_frequency.sample(vec![("engine", hw.name.to_string())], hw.frequency * 1e6);
+    }
+    families.push(engine_running);
+    families.push(engine_frequency);
+
+    let mut output = String::new();
+    for family in &families {
+        family.write(&mut output);
+    }
+    output.push_str("# EOF\n");
+
+    output
+}
+
+fn respond(stream: TcpStream, snapshot: &Mutex<Snapshot>) -> Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut reader = BufReader::new(&stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Drain the headers
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, content_type, body) = match path {
+        "/metrics" => {
+            let snapshot = snapshot.lock().unwrap();
+            ("200 OK", CONTENT_TYPE, metrics(&snapshot))
+        }
+        _ => (
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            "Not found, the metrics are served on /metrics\n".to_string(),
+        ),
+    };
+
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+/// Writes the metrics to `<dir>/tegratop.prom` at every tick for the
+/// node_exporter textfile collector. The file is replaced atomically so a scrape never sees a partial file.
+pub fn textfile(mut collectors: Collectors, dir: &Path, mut ticker: Ticker) -> Result<()> {
+    let path = dir.join("tegratop.prom");
+    // The textfile collector only reads `*.prom` files, so the temporary file is ignored
+    let tmp_path = dir.join(format!("tegratop.prom.{}", process::id()));
+
+    while ticker.wait() {
+        collectors.refresh();
+
+        fs::write(&tmp_path, metrics(&collectors.snapshot()))
+            .context(format!("Failed to write to {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &path).context(format!(
+            "Failed to rename {} to {}",
+            tmp_path.display(),
+            path.display()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Serves the metrics on `http://<addr>/metrics`, refreshing them every `interval` milliseconds.
+pub fn serve(mut collectors: Collectors, addr: SocketAddr, interval: u64) -> Result<()> {
+    let listener = TcpListener::bind(addr).context(format!("Failed to listen on {}", addr))?;
+    info!("Serving metrics on http://{}/metrics", addr);
+
+    let snapshot = Arc::new(Mutex::new(collectors.snapshot()));
+
+    {
+        let snapshot = snapshot.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(Duration::from_millis(interval));
+                collectors.refresh();
+                *snapshot.lock().unwrap() = collectors.snapshot();
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = respond(stream, &snapshot) {
+                    error!("{}", e);
+                }
+            }
+            Err(e) => error!("{}", e),
+        }
+    }
+
+    Ok(())
+}
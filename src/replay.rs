@@ -0,0 +1,149 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    time::Instant,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{json::Sample, snapshot::Snapshot};
+
+const SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/// Plays back a recording made with `tegratop record` or `--stream`.
+#[derive(Debug)]
+pub struct Player {
+    samples: Vec<Sample>,
+    pub position: usize,
+    /// Seconds since the first sample
+    pub clock: f64,
+    speed: usize,
+    pub paused: bool,
+    last_update: Instant,
+}
+
+impl Player {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+
+        let mut samples: Vec<Sample> = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let sample: Sample = serde_json::from_str(&line).context(format!(
+                "Invalid sample at {}:{}",
+                path.display(),
+                index + 1
+            ))?;
+            samples.push(sample);
+        }
+
+        if samples.is_empty() {
+            bail!("No samples found in {}", path.display());
+        }
+
+        Ok(Self {
+            samples,
+            position: 0,
+            clock: 0.0,
+            speed: 2,
+            paused: false,
+            last_update: Instant::now(),
+        })
+    }
+
+    /// Number of samples, `load` rejects the empty recordings
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.samples[self.position].snapshot
+    }
+
+    pub fn speed(&self) -> f64 {
+        SPEEDS[self.speed]
+    }
+
+    /// Length of the recording in seconds
+    pub fn duration(&self) -> f64 {
+        self.offset(self.samples.len() - 1)
+    }
+
+    /// Seconds between the first and the current sample
+    pub fn elapsed(&self) -> f64 {
+        self.offset(self.position)
+    }
+
+    fn offset(&self, position: usize) -> f64 {
+        self.samples[position].monotonic - self.samples[0].monotonic
+    }
+
+    /// The samples after `time` up to the current one, with their offset.
+    /// Only the current sample when `time` is not before it, e.g. after stepping back.
+    pub fn since(&self, time: Option<f64>) -> impl Iterator<Item = (f64, &Snapshot)> {
+        let start = match time {
+            Some(time) if time < self.elapsed() => {
+                (0..self.position).find(|position| self.offset(*position) > time)
+            }
+            _ => None,
+        }
+        .unwrap_or(self.position);
+
+        (start..=self.position)
+            .map(|position| (self.offset(position), &self.samples[position].snapshot))
+    }
+
+    /// Moves the clock forward by the real time elapsed since the last call, scaled by the speed.
+    pub fn advance(&mut self) {
+        let now = Instant::now();
+        if !self.paused {
+            self.clock += now.duration_since(self.last_update).as_secs_f64() * self.speed();
+        }
+        self.last_update = now;
+
+        while self.position + 1 < self.samples.len() && self.offset(self.position + 1) <= self.clock
+        {
+            self.position += 1;
+        }
+
+        if self.position + 1 == self.samples.len() {
+            self.clock = self.duration();
+            self.paused = true;
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        // Start over when the end is reached
+        if self.paused && self.position + 1 == self.samples.len() {
+            self.position = 0;
+            self.clock = 0.0;
+        }
+        self.paused = !self.paused;
+        self.last_update = Instant::now();
+    }
+
+    pub fn step_forward(&mut self) {
+        self.paused = true;
+        self.position = (self.position + 1).min(self.samples.len() - 1);
+        self.clock = self.offset(self.position);
+    }
+
+    pub fn step_backward(&mut self) {
+        self.paused = true;
+        self.position = self.position.saturating_sub(1);
+        self.clock = self.offset(self.position);
+    }
+
+    pub fn speed_up(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slow_down(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+}
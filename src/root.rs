@@ -0,0 +1,22 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Sets the directory that every absolute `/proc`, `/sys` and `/etc` path is resolved under.
+/// Only the first call has an effect, it must happen before any collector is created.
+pub fn set(root: impl Into<PathBuf>) {
+    let _ = ROOT.set(root.into());
+}
+
+pub fn get() -> &'static Path {
+    ROOT.get_or_init(|| PathBuf::from("/"))
+}
+
+/// Resolves an absolute system path against the configured root.
+pub fn path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    get().join(path.strip_prefix("/").unwrap_or(path))
+}
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::BoardSnapshot, cpu::CpuSnapshot, disk::DiskSnapshot, engine::EngineSnapshot,
+    fan::FanSnapshot, gpu::GpuSnapshot, memory::MemorySnapshot, network::NetworkSnapshot,
+    power::PowerSnapshot, system::SystemSnapshot, thermal::ThermalSnapshot,
+};
+
+/// The metrics of all the subsystems at a given time.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub board: BoardSnapshot,
+    pub cpu: CpuSnapshot,
+    pub disk: DiskSnapshot,
+    pub engine: EngineSnapshot,
+    pub fan: FanSnapshot,
+    pub gpu: GpuSnapshot,
+    pub memory: MemorySnapshot,
+    pub network: NetworkSnapshot,
+    pub power: PowerSnapshot,
+    pub system: SystemSnapshot,
+    pub thermal: ThermalSnapshot,
+}
@@ -6,6 +6,7 @@ use std::io::Seek;
 
 use anyhow::Result;
 
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
     layout::{Constraint, Rect},
@@ -13,32 +14,36 @@ use ratatui::{
     widgets::{Block, Borders, Cell, Padding, Row, Table},
 };
 
+use crate::{collector::Collector, root};
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Default)]
 pub struct System {
     loadavg: Option<Loadavg>,
     uptime: Option<Uptime>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SystemSnapshot {
+    pub loadavg: Option<[f64; 3]>,
+    /// Seconds
+    pub uptime: Option<u64>,
+}
+
 #[derive(Debug)]
 pub struct Uptime {
     file: File,
-    value: String,
+    value: u64,
 }
 
 impl Uptime {
     fn new() -> Result<Self> {
-        let mut file = File::open("/proc/uptime").context("Failed to open /proc/uptime")?;
+        let path = root::path("/proc/uptime");
+        let mut file = File::open(&path).context(format!("Failed to open {}", path.display()))?;
         let mut buffer = String::new();
         file.read_to_string(&mut buffer)?;
 
-        let uptime_in_seconds = buffer.trim().split('.').next().unwrap().parse::<f64>()?;
-
-        let days = (uptime_in_seconds / (60.0 * 60.0 * 24.0)) as u64;
-        let hours = ((uptime_in_seconds / (60.0 * 60.0)) % 24.0) as u64;
-        let minutes = ((uptime_in_seconds / 60.0) % 60.0) as u64;
-        let seconds = (uptime_in_seconds % 60.0) as u64;
-
-        let value = format!("{}days, {}h, {}min, {}s", days, hours, minutes, seconds);
+        let value = buffer.trim().split('.').next().unwrap().parse::<u64>()?;
 
         Ok(Self { file, value })
     }
@@ -48,13 +53,7 @@ impl Uptime {
         let mut buffer = String::new();
         self.file.read_to_string(&mut buffer)?;
 
-        let uptime_in_seconds = buffer.trim().split('.').next().unwrap().parse::<f64>()?;
-
-        let days = (uptime_in_seconds / (60.0 * 60.0 * 24.0)) as u64;
-        let hours = ((uptime_in_seconds / (60.0 * 60.0)) % 24.0) as u64;
-        let minutes = ((uptime_in_seconds / 60.0) % 60.0) as u64;
-        let seconds = (uptime_in_seconds % 60.0) as u64;
-        self.value = format!("{}days, {}h, {}min, {}s", days, hours, minutes, seconds);
+        self.value = buffer.trim().split('.').next().unwrap().parse::<u64>()?;
 
         Ok(())
     }
@@ -68,7 +67,8 @@ pub struct Loadavg {
 
 impl Loadavg {
     fn new() -> Result<Self> {
-        let mut file = File::open("/proc/loadavg").context("Failed to open /proc/loadavg")?;
+        let path = root::path("/proc/loadavg");
+        let mut file = File::open(&path).context(format!("Failed to open {}", path.display()))?;
         let mut buffer = String::new();
         file.read_to_string(&mut buffer)?;
 
@@ -100,8 +100,10 @@ impl Loadavg {
     }
 }
 
-impl System {
-    pub fn new() -> Self {
+impl Collector for System {
+    type Snapshot = SystemSnapshot;
+
+    fn init() -> Result<Self> {
         let loadavg = Loadavg::new().map_or_else(
             |e| {
                 error!("{}", e);
@@ -118,10 +120,10 @@ impl System {
             Some,
         );
 
-        Self { loadavg, uptime }
+        Ok(Self { loadavg, uptime })
     }
 
-    pub fn refresh(&mut self) {
+    fn refresh(&mut self) {
         if let Some(load_avg) = &mut self.loadavg
             && let Err(e) = load_avg.refresh()
         {
@@ -135,23 +137,38 @@ impl System {
         };
     }
 
+    fn snapshot(&self) -> SystemSnapshot {
+        SystemSnapshot {
+            loadavg: self.loadavg.as_ref().map(|loadavg| loadavg.value),
+            uptime: self.uptime.as_ref().map(|uptime| uptime.value),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl SystemSnapshot {
     pub fn render(&self, frame: &mut Frame, block: Rect) {
         let rows: [Row; 3] = [
             Row::new(vec![
                 Cell::new("Load avg").style(Style::default().bold()),
                 Cell::new(match &self.loadavg {
-                    Some(loadavg) => format!(
-                        "{:.2} {:.2} {:.2}",
-                        loadavg.value[0], loadavg.value[1], loadavg.value[2]
-                    ),
+                    Some(loadavg) => {
+                        format!("{:.2} {:.2} {:.2}", loadavg[0], loadavg[1], loadavg[2])
+                    }
                     None => " - ".to_string(),
                 }),
             ]),
             Row::new(vec!["", ""]),
             Row::new(vec![
                 Cell::new("Uptime").style(Style::default().bold()),
-                Cell::new(match &self.uptime {
-                    Some(uptime) => uptime.value.to_string(),
+                Cell::new(match self.uptime {
+                    Some(uptime) => {
+                        let days = uptime / (60 * 60 * 24);
+                        let hours = (uptime / (60 * 60)) % 24;
+                        let minutes = (uptime / 60) % 60;
+                        let seconds = uptime % 60;
+                        format!("{}days, {}h, {}min, {}s", days, hours, minutes, seconds)
+                    }
                     None => " - ".to_string(),
                 }),
             ]),
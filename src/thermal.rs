@@ -5,13 +5,21 @@ use std::{
     io::{Read, Seek},
 };
 
+#[cfg(feature = "tui")]
 use ratatui::{
     Frame,
     layout::{Constraint, Rect},
     style::{Style, Stylize},
-    widgets::{Block, Borders, Padding, Row, Table},
+    widgets::{Block, Borders, Cell, Padding, Row, Table},
 };
 
+use crate::{collector::Collector, root};
+#[cfg(feature = "tui")]
+use crate::{config::Config, history::Series};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tui")]
+use std::collections::HashMap;
+
 #[derive(Debug, Default)]
 pub struct Thermal {
     pub sensors: Vec<Sensor>,
@@ -19,8 +27,20 @@ pub struct Thermal {
 #[derive(Debug)]
 pub struct Sensor {
     file: File,
-    name: String,
-    temperature: f32,
+    pub name: String,
+    pub temperature: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThermalSnapshot {
+    pub sensors: Vec<SensorSnapshot>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SensorSnapshot {
+    pub name: String,
+    /// °C
+    pub temperature: f32,
 }
 
 impl Sensor {
@@ -34,22 +54,13 @@ impl Sensor {
 }
 
 impl Thermal {
-    pub fn new() -> Self {
-        let sensors = match Thermal::init() {
-            Ok(sensors) => sensors,
-            Err(e) => {
-                error!("{}", e);
-                Vec::new()
-            }
-        };
-
-        Self { sensors }
-    }
-
-    pub fn init() -> Result<Vec<Sensor>> {
+    pub fn sensors() -> Result<Vec<Sensor>> {
         let mut sensors: Vec<Sensor> = Vec::new();
-        let entries = fs::read_dir("/sys/devices/virtual/thermal/")
-            .context("Failed to read from the directory /sys/devices/virtual/thermal")?;
+        let thermal_path = root::path("/sys/devices/virtual/thermal/");
+        let entries = fs::read_dir(&thermal_path).context(format!(
+            "Failed to read from the directory {}",
+            thermal_path.display()
+        ))?;
 
         for entry in entries {
             let entry = entry?;
@@ -93,8 +104,18 @@ impl Thermal {
 
         Ok(sensors)
     }
+}
+
+impl Collector for Thermal {
+    type Snapshot = ThermalSnapshot;
+
+    fn init() -> Result<Self> {
+        Ok(Self {
+            sensors: Thermal::sensors()?,
+        })
+    }
 
-    pub fn refresh(&mut self) {
+    fn refresh(&mut self) {
         for sensor in &mut self.sensors {
             if let Err(e) = sensor.refresh() {
                 error!("{}", e);
@@ -102,29 +123,99 @@ impl Thermal {
         }
     }
 
-    pub fn render(&self, frame: &mut Frame, block: Rect) {
-        let rows: Vec<Row> = self
-            .sensors
+    fn snapshot(&self) -> ThermalSnapshot {
+        ThermalSnapshot {
+            sensors: self
+                .sensors
+                .iter()
+                .map(|sensor| SensorSnapshot {
+                    name: sensor.name.clone(),
+                    temperature: sensor.temperature,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl ThermalSnapshot {
+    /// The sensors that are not hidden in the configuration
+    pub fn visible<'a>(&'a self, config: &'a Config) -> impl Iterator<Item = &'a SensorSnapshot> {
+        self.sensors
             .iter()
+            .filter(|sensor| !config.thermal.is_hidden(&sensor.name))
+    }
+
+    pub fn render(
+        &self,
+        frame: &mut Frame,
+        block: Rect,
+        history: &HashMap<String, Series>,
+        config: &Config,
+    ) {
+        let level = self
+            .visible(config)
+            .filter(|sensor| sensor.temperature >= -25.0)
+            .map(|sensor| {
+                config
+                    .thresholds
+                    .temperature
+                    .level(sensor.temperature as f64)
+            })
+            .max()
+            .unwrap_or_default();
+
+        let name_width = self
+            .visible(config)
+            .map(|sensor| config.thermal.name(&sensor.name).chars().count())
+            .max()
+            .unwrap_or_default()
+            .max(6) as u16;
+
+        // Borders, padding and the other columns
+        let history_width = block.width.saturating_sub(2 + 2 + name_width + 1 + 11 + 1);
+
+        let rows: Vec<Row> = self
+            .visible(config)
             .map(|sensor| {
                 let temperature = match sensor.temperature {
-                    t if t < -25.0 => " - ".to_string(),
-                    t => format!("{:.1} C", t),
+                    t if t < -25.0 => Cell::new(" - "),
+                    t => Cell::new(config.temperature_unit.format(t))
+                        .style(config.thresholds.temperature.level(t as f64).style()),
                 };
 
-                Row::new(vec![sensor.name.to_owned(), temperature])
+                // Scaled on at least 10 C so the noise of the sensors stays flat
+                let sparkline = history
+                    .get(&sensor.name)
+                    .and_then(|series| {
+                        let min = series.min()?;
+                        let max = series.max()?.max(min + 10.0);
+                        Some(series.sparkline(history_width as usize, min, max))
+                    })
+                    .unwrap_or_default();
+
+                Row::new(vec![
+                    Cell::new(config.thermal.name(&sensor.name).to_string()),
+                    temperature,
+                    Cell::new(sparkline),
+                ])
             })
             .collect();
 
-        let widths = [Constraint::Length(8), Constraint::Length(11)];
+        let widths = [
+            Constraint::Length(name_width),
+            Constraint::Length(11),
+            Constraint::Length(history_width),
+        ];
 
         let table = Table::new(rows, widths)
-            .header(Row::new(vec!["Sensor", "Temperature"]).style(Style::new().bold()))
+            .header(Row::new(vec!["Sensor", "Temperature", "History"]).style(Style::new().bold()))
             .block(
                 Block::default()
                     .title("Thermal")
                     .title_style(Style::new().bold())
                     .borders(Borders::ALL)
+                    .border_style(level.border_style())
                     .padding(Padding::horizontal(1)),
             );
         frame.render_widget(table, block);
@@ -0,0 +1,37 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Drift free schedule of the samples for the modes without TUI.
+#[derive(Debug)]
+pub struct Ticker {
+    interval: Duration,
+    next_tick: Instant,
+    once: bool,
+    ticks: u64,
+}
+
+impl Ticker {
+    /// Ticks every `interval` milliseconds, only once if `once` is set.
+    pub fn new(interval: u64, once: bool) -> Self {
+        Self {
+            interval: Duration::from_millis(interval),
+            next_tick: Instant::now(),
+            once,
+            ticks: 0,
+        }
+    }
+
+    /// Sleeps until the next tick, returns false when there is none left.
+    pub fn wait(&mut self) -> bool {
+        if self.once && self.ticks > 0 {
+            return false;
+        }
+        self.ticks += 1;
+
+        self.next_tick += self.interval;
+        thread::sleep(self.next_tick.saturating_duration_since(Instant::now()));
+        true
+    }
+}
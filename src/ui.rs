@@ -1,82 +1,343 @@
+use std::io;
+
 use ratatui::{
-    Frame,
-    layout::{Constraint, Direction, Layout},
+    Frame, Terminal,
+    backend::{Backend, ClearType, WindowSize},
+    buffer::{Buffer, Cell},
+    layout::{Constraint, Direction, Layout, Position, Rect, Size},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::Paragraph,
+};
+use serde::Deserialize;
+use strum_macros::{Display, EnumIter, EnumString, VariantNames};
+
+use crate::{
+    app::{App, AppResult, Source, View},
+    config::Config,
+    replay::Player,
+    snapshot::Snapshot,
 };
 
-use crate::app::App;
+/// The panels of the main view, in their default order
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Display, EnumString, EnumIter, VariantNames, Deserialize,
+)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Panel {
+    Board,
+    Cpu,
+    Memory,
+    Gpu,
+    System,
+    Fan,
+    Disk,
+    Engines,
+    Network,
+    Thermal,
+    Power,
+}
+
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    )
+}
+
+fn render_replay(player: &Player, frame: &mut Frame, block: Rect) {
+    let state = if player.paused { "Paused" } else { "Playing" };
+
+    let line = Line::from(vec![
+        Span::styled(" Replay ", Style::new().bold().reversed()),
+        Span::raw(format!(
+            " {}  {}/{}  {} / {}  x{} ",
+            state,
+            player.position + 1,
+            player.sample_count(),
+            format_duration(player.clock),
+            format_duration(player.duration()),
+            player.speed(),
+        )),
+        Span::styled(
+            " Space/p: play/pause  ←/→: step  +/-: speed",
+            Style::new().dim(),
+        ),
+    ]);
+
+    frame.render_widget(Paragraph::new(line), block);
+}
+
+/// Banner with the firing alerts at the top of the view, returns the remaining area
+fn render_alerts(app: &App, frame: &mut Frame, area: Rect) -> Rect {
+    let lines = app
+        .alerts
+        .firing()
+        .map(|alert| {
+            Line::from(vec![
+                Span::styled(" Alert ", Style::new().bold().reversed()),
+                Span::raw(format!(
+                    " {}  {}",
+                    alert.name,
+                    alert.value.as_deref().unwrap_or_default()
+                )),
+            ])
+            .red()
+        })
+        .collect::<Vec<Line>>();
+
+    if lines.is_empty() {
+        return area;
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(lines.len() as u16), Constraint::Min(1)])
+        .split(area);
+    frame.render_widget(Paragraph::new(lines), chunks[0]);
+
+    chunks[1]
+}
 
 pub fn render(app: &mut App, frame: &mut Frame) {
-    let cpu_block_length = (&app.cpu.cores.len() + 1) as u16;
-    let thermal_block_length = app.thermal.sensors.len();
-    let engines_block_length = app.engine.hws.len();
-    let power_block_length = app.power.channels.len();
-    let engine_block_length = app.engine.hws.len() as u16;
-
-    let container_length = std::cmp::max(
-        power_block_length,
-        std::cmp::max(engines_block_length, thermal_block_length),
-    ) as u16
-        + 3;
-
-    let (
-        board_block,
-        cpu_block,
-        memory_block,
-        gpu_block,
-        system_block,
-        fan_block,
-        disk_block,
-        engine_block,
-        container_block,
-    ) = {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(
-                [
-                    Constraint::Length(5),
-                    Constraint::Length(cpu_block_length),
-                    Constraint::Length(7),                   // memory
-                    Constraint::Length(3),                   // gpu
-                    Constraint::Length(5),                   // system
-                    Constraint::Length(4),                   // fan
-                    Constraint::Length(4),                   // disk
-                    Constraint::Length(engine_block_length), // network
-                    Constraint::Length(container_length),
-                    Constraint::Min(1),
-                ]
-                .as_ref(),
-            )
-            .split(frame.area());
-        (
-            chunks[0], chunks[1], chunks[2], chunks[3], chunks[4], chunks[5], chunks[6], chunks[7],
-            chunks[8],
-        )
+    let snapshot = &app.snapshot;
+
+    let area = match &app.source {
+        Source::Replay(player) => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(1), Constraint::Length(1)])
+                .split(frame.area());
+            render_replay(player, frame, chunks[1]);
+            chunks[0]
+        }
+        Source::Live(_) => frame.area(),
     };
 
-    let (network_block, thermal_block, power_block) = {
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(
-                [
-                    Constraint::Percentage(33),
-                    Constraint::Percentage(33),
-                    Constraint::Percentage(33),
-                ]
-                .as_ref(),
-            )
-            .split(container_block);
-        (chunks[0], chunks[1], chunks[2])
+    let area = render_alerts(app, frame, area);
+
+    match app.view {
+        View::Main => render_panels(app, frame, area),
+        View::Charts => app
+            .chart
+            .render(snapshot, &app.history, &app.config, frame, area),
+        View::Processes => app.process.render(&app.processes.processes, frame, area),
+    }
+
+    if let Some(popup) = &app.popup {
+        popup.render(frame);
+    }
+
+    if app.no_color {
+        strip_colors(frame.buffer_mut());
+    }
+}
+
+/// Height of a panel, borders included
+fn height(panel: Panel, snapshot: &Snapshot, config: &Config) -> u16 {
+    let height = match panel {
+        Panel::Board => 5,
+        // The cores are split in two columns, with a gap between the bars
+        Panel::Cpu => 2 * snapshot.cpu.cores.len().div_ceil(2) + 1,
+        Panel::Memory => 7,
+        Panel::Gpu => 3,
+        Panel::System => 5,
+        // The curves of the profiles need a few more lines
+        Panel::Fan => {
+            let fans = snapshot.fan.fans.len().max(1) + 3;
+            match snapshot.fan.fans.iter().any(|fan| !fan.curve.is_empty()) {
+                true => fans.max(8),
+                false => fans,
+            }
+        }
+        Panel::Disk => 4,
+        Panel::Engines => snapshot.engine.hws.len().div_ceil(2) + 3,
+        Panel::Network => snapshot.network.interfaces.len() + 3,
+        Panel::Thermal => snapshot.thermal.visible(config).count() + 3,
+        // The nvpmodel mode takes two lines above the table
+        Panel::Power => snapshot.power.visible(config).count() + 5,
     };
+    height.max(3) as u16
+}
+
+/// Network, Thermal and Power are narrow tables, they share a row
+fn is_narrow(panel: Panel) -> bool {
+    matches!(panel, Panel::Network | Panel::Thermal | Panel::Power)
+}
+
+/// The panels of the main view, one per row except the narrow ones
+fn rows(panels: &[Panel]) -> Vec<Vec<Panel>> {
+    let mut rows: Vec<Vec<Panel>> = Vec::new();
+    let mut narrow_row: Option<usize> = None;
+
+    for panel in panels {
+        if is_narrow(*panel) {
+            match narrow_row {
+                Some(index) => rows[index].push(*panel),
+                None => {
+                    narrow_row = Some(rows.len());
+                    rows.push(vec![*panel]);
+                }
+            }
+        } else {
+            rows.push(vec![*panel]);
+        }
+    }
+
+    rows
+}
+
+fn render_panels(app: &App, frame: &mut Frame, area: Rect) {
+    let snapshot = &app.snapshot;
+    let history = &app.history;
+
+    let rows = rows(&app.panels);
+
+    let mut constraints: Vec<Constraint> = rows
+        .iter()
+        .map(|row| {
+            Constraint::Length(
+                row.iter()
+                    .map(|panel| height(*panel, snapshot, &app.config))
+                    .max()
+                    .unwrap_or_default(),
+            )
+        })
+        .collect();
+    constraints.push(Constraint::Min(0));
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .split(area);
+
+    for (row, row_block) in rows.iter().zip(chunks.iter()) {
+        let blocks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(row.iter().map(|_| Constraint::Ratio(1, row.len() as u32)))
+            .split(*row_block);
+
+        for (panel, block) in row.iter().zip(blocks.iter()) {
+            let block = *block;
+            match panel {
+                Panel::Board => snapshot.board.render(frame, block),
+                Panel::Cpu => snapshot.cpu.render(frame, block, &history.cpu, &app.config),
+                Panel::Memory => {
+                    snapshot
+                        .memory
+                        .render(frame, block, &history.ram, &history.swap, &app.config)
+                }
+                Panel::Gpu => snapshot.gpu.render(frame, block, &history.gpu, &app.config),
+                Panel::System => snapshot.system.render(frame, block),
+                Panel::Fan => snapshot.fan.render(frame, block, &app.config),
+                Panel::Disk => snapshot.disk.render(frame, block),
+                Panel::Engines => snapshot.engine.render(frame, block),
+                Panel::Network => snapshot.network.render(frame, block),
+                Panel::Thermal => {
+                    snapshot
+                        .thermal
+                        .render(frame, block, &history.thermal, &app.config)
+                }
+                Panel::Power => snapshot
+                    .power
+                    .render(frame, block, &history.power, &app.config),
+            }
+        }
+    }
+}
+
+/// Keeps the modifiers, e.g. bold, but drops the colors
+fn strip_colors(buffer: &mut Buffer) {
+    for cell in buffer.content.iter_mut() {
+        cell.fg = Color::Reset;
+        cell.bg = Color::Reset;
+    }
+}
+
+/// Backend of a fixed size that discards the output, the frame is read from the terminal buffer instead.
+struct BufferBackend {
+    size: Size,
+}
+
+impl Backend for BufferBackend {
+    fn draw<'a, I>(&mut self, _content: I) -> io::Result<()>
+    where
+        I: Iterator<Item = (u16, u16, &'a Cell)>,
+    {
+        Ok(())
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn get_cursor_position(&mut self) -> io::Result<Position> {
+        Ok(Position::ORIGIN)
+    }
+
+    fn set_cursor_position<P: Into<Position>>(&mut self, _position: P) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn clear_region(&mut self, _clear_type: ClearType) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<Size> {
+        Ok(self.size)
+    }
+
+    fn window_size(&mut self) -> io::Result<WindowSize> {
+        Ok(WindowSize {
+            columns_rows: self.size,
+            pixels: Size::default(),
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Renders a single frame of the main view as plain text, e.g. for `--once`.
+pub fn print(app: &mut App, width: u16) -> AppResult<String> {
+    let panels_height: u16 = rows(&app.panels)
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|panel| height(*panel, &app.snapshot, &app.config))
+                .max()
+                .unwrap_or_default()
+        })
+        .sum();
+
+    let mut terminal = Terminal::new(BufferBackend {
+        size: Size::new(width, panels_height),
+    })?;
+    let buffer = terminal
+        .draw(|frame| render_panels(app, frame, frame.area()))?
+        .buffer;
+
+    let mut text = String::new();
+    for y in 0..buffer.area.height {
+        let line: String = (0..buffer.area.width)
+            .map(|x| buffer[(x, y)].symbol())
+            .collect();
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
 
-    app.cpu.render(frame, cpu_block);
-    app.memory.render(frame, memory_block);
-    app.network.render(frame, network_block);
-    app.gpu.render(frame, gpu_block);
-    app.engine.render(frame, engine_block);
-    app.thermal.render(frame, thermal_block);
-    app.fan.render(frame, fan_block);
-    app.disk.render(frame, disk_block);
-    app.power.render(frame, power_block);
-    app.system.render(frame, system_block);
-    app.board.render(frame, board_block);
+    Ok(text)
 }
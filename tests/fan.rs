@@ -0,0 +1,130 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::OnceLock,
+};
+
+use tegratop::{
+    collector::Collector,
+    fan::{self, Control, Fan},
+    root,
+};
+
+/// The tests write to the tree, they run on a copy of the fixture
+fn setup() -> &'static Path {
+    static ROOT: OnceLock<PathBuf> = OnceLock::new();
+    ROOT.get_or_init(|| {
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/fan");
+        let dir = std::env::temp_dir().join(format!("tegratop-fan-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        copy(&fixture, &dir).unwrap();
+        root::set(&dir);
+        dir
+    })
+}
+
+fn copy(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let path = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy(&entry.path(), &path)?;
+        } else {
+            fs::copy(entry.path(), path)?;
+        }
+    }
+    Ok(())
+}
+
+fn profiles() -> Vec<Option<String>> {
+    Fan::new()
+        .snapshot()
+        .fans
+        .into_iter()
+        .map(|fan| fan.profile)
+        .collect()
+}
+
+#[test]
+fn set_profiles() {
+    let root = setup();
+    let conf_path = root.join("etc/nvfancontrol.conf");
+    let conf = fs::read_to_string(&conf_path).unwrap();
+
+    let snapshot = Fan::new().snapshot();
+    assert_eq!(snapshot.fans.len(), 2);
+    for fan in &snapshot.fans {
+        assert_eq!(fan.available_profiles, ["quiet", "cool"]);
+    }
+    // The status saved by nvfancontrol takes precedence over FAN_DEFAULT_PROFILE
+    assert_eq!(
+        profiles(),
+        [Some("cool".to_string()), Some("quiet".to_string())]
+    );
+    assert_eq!(snapshot.fans[0].curve.len(), 4);
+    assert_eq!(snapshot.fans[0].temperature, Some(52.0));
+
+    // The first fan keeps the profile it runs, the second switches
+    fan::set_profiles(
+        "true",
+        &[Some("cool".to_string()), Some("cool".to_string())],
+    )
+    .unwrap();
+
+    let written = fs::read_to_string(&conf_path).unwrap();
+    let changed: Vec<(&str, &str)> = conf
+        .lines()
+        .zip(written.lines())
+        .filter(|(before, after)| before != after)
+        .collect();
+    assert_eq!(
+        changed,
+        [
+            ("\tFAN_DEFAULT_PROFILE quiet", "\tFAN_DEFAULT_PROFILE cool"),
+            ("\tFAN_DEFAULT_PROFILE quiet", "\tFAN_DEFAULT_PROFILE cool"),
+        ]
+    );
+    assert_eq!(conf.lines().count(), written.lines().count());
+
+    assert!(!root.join("var/lib/nvfancontrol/status").exists());
+    let files: Vec<_> = fs::read_dir(root.join("etc")).unwrap().collect();
+    assert_eq!(files.len(), 1);
+
+    assert_eq!(
+        profiles(),
+        [Some("cool".to_string()), Some("cool".to_string())]
+    );
+}
+
+#[test]
+fn set_profiles_fails_with_the_service() {
+    setup();
+    assert!(fan::set_profiles("false", &[Some("cool".to_string())]).is_err());
+}
+
+#[test]
+fn set_pwm() {
+    let root = setup();
+    let hwmon = root.join("sys/class/hwmon/hwmon2");
+    let read = |file: &str| fs::read_to_string(hwmon.join(file)).unwrap();
+    let control = || Fan::new().snapshot().fans[1].control;
+
+    // pwm1_enable 2 is the automatic mode of the driver
+    assert_eq!(control(), Some(Control::Auto));
+
+    fan::set_pwm("true", "pwmfan1", 100).unwrap();
+    assert_eq!(read("pwm1"), "100");
+    assert_eq!(read("pwm1_enable"), "1");
+    assert_eq!(control(), Some(Control::Manual));
+
+    fan::set_pwm("true", "pwmfan1", 200).unwrap();
+    assert_eq!(read("pwm1"), "200");
+
+    fan::set_auto("true").unwrap();
+    assert_eq!(read("pwm1_enable"), "2");
+    assert_eq!(control(), Some(Control::Auto));
+
+    assert!(fan::set_pwm("true", "fan9", 100).is_err());
+}
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use tegratop::{
+    collector::Collector,
+    power::{self, ClockLimit, ModeChange, Power},
+    root,
+};
+
+fn fixture(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(path)
+}
+
+fn nvpmodel() -> String {
+    fixture("nvpmodel").display().to_string()
+}
+
+#[test]
+fn set_mode_without_reboot() {
+    assert_eq!(
+        power::set_mode(&nvpmodel(), 2, false).unwrap(),
+        ModeChange::Done
+    );
+}
+
+#[test]
+fn set_mode_declines_reboot() {
+    assert_eq!(
+        power::set_mode(&nvpmodel(), 1, false).unwrap(),
+        ModeChange::RebootRequired
+    );
+}
+
+#[test]
+fn set_mode_accepts_reboot() {
+    assert_eq!(
+        power::set_mode(&nvpmodel(), 1, true).unwrap(),
+        ModeChange::Done
+    );
+}
+
+#[test]
+fn set_mode_fails() {
+    let error = power::set_mode(&nvpmodel(), 9, false).unwrap_err();
+    assert!(error.to_string().contains("Invalid power mode 9"));
+}
+
+#[test]
+fn orin_modes() {
+    root::set(fixture("orin"));
+    let snapshot = Power::new().snapshot();
+
+    let names: Vec<&str> = snapshot
+        .available_modes
+        .iter()
+        .map(|mode| mode.name.as_str())
+        .collect();
+    assert_eq!(names, ["MAXN", "MODE_15W", "MODE_30W", "MODE_50W"]);
+    assert_eq!(snapshot.available_modes[0].power_budget, None);
+
+    // From pmode:0001 of the status file, not the default mode 2
+    let mode = snapshot.mode.unwrap();
+    assert_eq!(mode.id, 1);
+    assert_eq!(mode.power_budget, Some(15));
+
+    let online: Vec<usize> = mode
+        .cpu_online
+        .iter()
+        .filter(|(_, online)| *online)
+        .map(|(core, _)| *core)
+        .collect();
+    assert_eq!(online, [0, 1, 2, 3]);
+
+    let clock = |name: &str| mode.clocks.iter().find(|clock| clock.name == name).cloned();
+    // kHz for the CPU, Hz for the others
+    assert_eq!(
+        clock("CPU_A78_0"),
+        Some(ClockLimit {
+            name: "CPU_A78_0".to_string(),
+            min_frequency: Some(729),
+            max_frequency: Some(1113),
+        })
+    );
+    assert_eq!(clock("GPU").unwrap().max_frequency, Some(420));
+    assert_eq!(clock("GPU").unwrap().min_frequency, None);
+    assert_eq!(clock("PVA0_VPS").unwrap().max_frequency, None);
+
+    assert!(mode.settings.contains(&(
+        "TPC_POWER_GATING TPC_PG_MASK".to_string(),
+        "254".to_string()
+    )));
+}